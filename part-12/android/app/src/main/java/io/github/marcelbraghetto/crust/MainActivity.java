@@ -2,6 +2,11 @@ package io.github.marcelbraghetto.crust;
 
 import org.libsdl.app.SDLActivity;
 
+import java.io.BufferedReader;
+import java.io.IOException;
+import java.io.InputStreamReader;
+import java.nio.charset.StandardCharsets;
+
 public class MainActivity extends SDLActivity {
     @Override
     protected String[] getLibraries() {
@@ -9,7 +14,16 @@ public class MainActivity extends SDLActivity {
                 "hidapi",
                 "SDL2",
                 "SDL2_image",
-                "crustlib"
+                getRustLibrary()
         };
     }
+
+    // crust-build writes the name of the Rust library it compiled into the assets, however the build was started.
+    private String getRustLibrary() {
+        try (BufferedReader reader = new BufferedReader(new InputStreamReader(getAssets().open("rust_library.txt"), StandardCharsets.UTF_8))) {
+            return reader.readLine().trim();
+        } catch (IOException e) {
+            throw new IllegalStateException("Missing 'rust_library.txt' asset, build the app via crust-build or its 'rustBuild' task.", e);
+        }
+    }
 }
@@ -1,45 +1,195 @@
 use crate::{
+    android_ndk::{self, Toolchain},
     core::{
-        context::Context, failable_unit::FailableUnit, io, logs, manifests, remote_zips, script::Script, scripts,
+        assets, build_report,
+        build_target::{BuildTarget, Resolved},
+        context::Context,
+        failable::Failable,
+        failable_unit::FailableUnit,
+        fingerprint::Fingerprint,
+        fingerprints, io, logs, manifests, outputs, remote_zips,
+        script::Script,
+        scripts,
+        target::Target,
         variant::Variant,
+        versions,
     },
     log_tag,
 };
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
-const SDL2_SOURCE_URL: &str = "https://www.libsdl.org/release/SDL2-2.0.14.zip";
 const SDL2_SOURCE_DIR: &str = "SDL";
 
-const SDL2_IMAGE_SOURCE_URL: &str = "https://www.libsdl.org/projects/SDL_image/release/SDL2_image-2.0.5.zip";
 const SDL2_IMAGE_SOURCE_DIR: &str = "SDL2_image";
 
-const CRUST_SO_FILE_NAME: &str = "libcrustlib.so";
+const APPLICATION_ID: &str = "io.github.marcelbraghetto.crust";
 
-pub fn build(context: &Context) -> FailableUnit {
-    context.print_summary();
+// Read by 'MainActivity' to know which library to load, see 'setup_assets'.
+const RUST_LIBRARY_ASSET: &str = "rust_library.txt";
 
-    let ndk_dir = PathBuf::from(std::env::var("ANDROID_NDK_ROOT")?);
-    logs::out(log_tag!(), &format!("Using Android NDK: {:?}", &ndk_dir));
+#[derive(Default)]
+pub struct AndroidTarget {
+    toolchain: Resolved<(Toolchain, Vec<Architecture>)>,
+}
+
+impl BuildTarget for AndroidTarget {
+    fn target(&self) -> Target {
+        Target::Android
+    }
+
+    fn prepare_dependencies(&self, context: &Context) -> FailableUnit {
+        // Resolving the toolchain up front means an unsupported NDK fails immediately rather than after the slow SDL compile.
+        let toolchain = android_ndk::locate(context, context.config.android.ndk_api_level)?;
+        logs::out(log_tag!(), &format!("Using Android NDK {}: {:?}", toolchain.version, &toolchain.ndk_dir));
+        build_report::record_third_party(
+            context,
+            "android-ndk",
+            &toolchain.version.to_string(),
+            &toolchain.ndk_dir.display().to_string(),
+        );
+
+        let architectures = architectures(context)?;
+        verify_toolchain(&toolchain, &architectures)?;
+
+        install_rust_dependencies(context, &architectures)?;
+        setup_sdl2(context, &toolchain, &architectures)?;
+        setup_assets(context)?;
+        setup_cargo_manifest(context)?;
+
+        self.toolchain.set((toolchain, architectures));
+        Ok(())
+    }
+
+    fn compile(&self, context: &Context) -> FailableUnit {
+        let resolved = self.toolchain.get()?;
+        let (toolchain, architectures) = &*resolved;
+
+        compile_rust_code(context, toolchain, architectures)?;
+        link_jni_libs(context)
+    }
+
+    fn create_output(&self, context: &Context) -> FailableUnit {
+        // Without '--assemble' the Gradle project picks up the linked JNI libraries itself, so there is nothing to gather.
+        if context.assemble {
+            assemble_app(context)?;
+        }
 
-    install_rust_dependencies()?;
-    setup_sdl2(context, &ndk_dir)?;
-    setup_assets(context)?;
-    setup_cargo_manifest(context)?;
-    compile_rust_code(context, &ndk_dir)?;
-    link_jni_libs(context)?;
+        Ok(())
+    }
+
+    fn run(&self, context: &Context) -> FailableUnit {
+        run(context)
+    }
+}
+
+fn run(context: &Context) -> FailableUnit {
+    // 'adb get-state' fails if there is no device or emulator attached so there would be nothing to install onto.
+    let device_state = context.executor.probe("adb get-state", &mut || {
+        scripts::execute_with_retries(&Script::new("adb get-state").timeout(Duration::from_secs(10)), true)
+            .ok()
+            .map(|output| output.stdout)
+    });
+
+    if device_state.is_none() {
+        logs::out(log_tag!(), "No Android device connected, skipping install.");
+        return Ok(());
+    }
+
+    // Only APKs can be installed directly, so even release builds are assembled as an APK here rather than an AAB.
+    let gradle_task = match context.variant {
+        Variant::Debug => "assembleDebug",
+        Variant::Release => "assembleRelease",
+    };
+
+    logs::out(log_tag!(), "Assembling Android application ...");
+    run_gradle(context, gradle_task)?;
+
+    let apk_path =
+        gradle_outputs_dir(context).join("apk").join(context.variant.id()).join(format!("app-{}.apk", context.variant.id()));
+
+    logs::out(log_tag!(), "Installing and launching Android application ...");
+    scripts::run(context, &Script::new(&format!("adb install -r {:?}", &apk_path)))?;
+    scripts::run(context, &Script::new(&format!("adb shell am start -n {}/.MainActivity", APPLICATION_ID)))
+}
+
+fn gradle_outputs_dir(context: &Context) -> PathBuf {
+    context.target_home_dir.join("app").join("build").join("outputs")
+}
+
+fn run_gradle(context: &Context, task: &str) -> FailableUnit {
+    let gradle_wrapper = if cfg!(target_os = "windows") {
+        "gradlew.bat"
+    } else {
+        "./gradlew"
+    };
+
+    // The Gradle project normally calls back into crust-build before compiling, but we have just done that build ourselves
+    // (possibly with a narrower ABI filter) so we ask it to skip its 'rustBuild' task.
+    // Gradle resolves its plugins and dependencies over the network, so a flaky connection is worth another attempt.
+    scripts::run(
+        context,
+        &Script::new(&format!("{} {} -PskipRustBuild", gradle_wrapper, task))
+            .working_dir(&context.target_home_dir)
+            .retries(1)
+            .log_file(&scripts::log_file(context, &format!("gradle-{}", task))),
+    )
+}
+
+fn assemble_app(context: &Context) -> FailableUnit {
+    let variant = context.variant.id();
+
+    // Debug builds are meant for side loading onto a device, release builds are bundles for uploading to the Play Store.
+    let (gradle_task, artifact, extension) = match context.variant {
+        Variant::Debug => ("assembleDebug", gradle_outputs_dir(context).join("apk").join(variant).join("app-debug.apk"), "apk"),
+        Variant::Release => {
+            ("bundleRelease", gradle_outputs_dir(context).join("bundle").join(variant).join("app-release.aab"), "aab")
+        }
+    };
+
+    logs::out(log_tag!(), &format!("Assembling Android application via Gradle '{}' ...", gradle_task));
+    run_gradle(context, gradle_task)?;
+
+    outputs::clean(context)?;
+    io::create_dir(context, &outputs::output_dir(context))?;
+    io::copy(
+        context,
+        &artifact,
+        &outputs::output_dir(context).join(format!("{}-{}.{}", &context.binary_name, variant, extension)),
+    )
+}
+
+fn architectures(context: &Context) -> Failable<Vec<Architecture>> {
+    context.config.android.architectures.iter().map(|jni_name| Architecture::resolve(jni_name)).collect()
+}
+
+pub fn check_toolchain(context: &Context, toolchain: &Toolchain) -> FailableUnit {
+    verify_toolchain(toolchain, &architectures(context)?)
+}
+
+fn verify_toolchain(toolchain: &Toolchain, architectures: &[Architecture]) -> FailableUnit {
+    for architecture in architectures {
+        toolchain.linker(&architecture.ndk_triple(), architecture.is_64_bit())?;
+        toolchain.archiver(&architecture.binutils_triple())?;
+        toolchain.strip(&architecture.binutils_triple())?;
+    }
 
     Ok(())
 }
 
-fn install_rust_dependencies() -> FailableUnit {
+pub fn rust_triples(context: &Context) -> Failable<Vec<String>> {
+    Ok(architectures(context)?.iter().map(|architecture| architecture.rust_triple()).collect())
+}
+
+fn install_rust_dependencies(context: &Context, architectures: &[Architecture]) -> FailableUnit {
     logs::out(log_tag!(), "Installing Android Rust targets ...");
-    scripts::run(&Script::new(&format!(
-        "rustup target add {} {} {} {}",
-        Architecture::ARMv8A.rust_triple(),
-        Architecture::ARMv7A.rust_triple(),
-        Architecture::X86.rust_triple(),
-        Architecture::X86_64.rust_triple(),
-    )))
+    scripts::run(
+        context,
+        &Script::new(&format!(
+            "rustup target add {}",
+            architectures.iter().map(|architecture| architecture.rust_triple()).collect::<Vec<String>>().join(" ")
+        ))
+        .retries(2),
+    )
 }
 
 fn ndk_project_dir(context: &Context) -> PathBuf {
@@ -50,16 +200,21 @@ fn compiled_libs_dir(context: &Context) -> PathBuf {
     ndk_project_dir(context).join("libs")
 }
 
-fn setup_sdl2(context: &Context, ndk_dir: &PathBuf) -> FailableUnit {
+fn so_file_name(context: &Context) -> Failable<String> {
+    Ok(format!("lib{}.so", context.package.library()?.name))
+}
+
+fn setup_sdl2(context: &Context, toolchain: &Toolchain, architectures: &[Architecture]) -> FailableUnit {
     let ndk_project_dir = ndk_project_dir(context);
 
-    io::create_dir(&ndk_project_dir)?;
+    io::create_dir(context, &ndk_project_dir)?;
 
-    remote_zips::fetch(SDL2_SOURCE_URL, SDL2_SOURCE_DIR, &ndk_project_dir)?;
-    remote_zips::fetch(SDL2_IMAGE_SOURCE_URL, SDL2_IMAGE_SOURCE_DIR, &ndk_project_dir)?;
+    remote_zips::fetch(context, &context.config.android.sdl2_source_url, SDL2_SOURCE_DIR, &ndk_project_dir)?;
+    remote_zips::fetch(context, &context.config.android.sdl2_image_source_url, SDL2_IMAGE_SOURCE_DIR, &ndk_project_dir)?;
 
     let sdl_java_source_symlink = context.target_home_dir.join("app").join("src").join("main").join("java").join("org");
     io::create_symlink(
+        context,
         &ndk_project_dir
             .join(SDL2_SOURCE_DIR)
             .join("android-project")
@@ -72,29 +227,56 @@ fn setup_sdl2(context: &Context, ndk_dir: &PathBuf) -> FailableUnit {
         &context.target_home_dir,
     )?;
 
-    io::write_string("include $(call all-subdir-makefiles)", &ndk_project_dir.join("Android.mk"))?;
-    logs::out(log_tag!(), "Compiling SDL NDK libraries (this may take a while!) ...");
-    io::delete(&compiled_libs_dir(context))?;
+    io::write_string(context, "include $(call all-subdir-makefiles)", &ndk_project_dir.join("Android.mk"))?;
 
-    scripts::run(
-        &Script::new(&format!(
-            "{:?} NDK_PROJECT_PATH={:?} APP_BUILD_SCRIPT={:?} APP_PLATFORM=android-21 APP_STL=c++_shared APP_ABI=all",
-            &ndk_dir.join("ndk-build"),
-            &ndk_project_dir,
-            &ndk_project_dir.join("Android.mk"),
-        ))
-        .working_dir(&ndk_project_dir),
-    )
+    let abis = architectures.iter().map(|architecture| architecture.jni_name()).collect::<Vec<String>>().join(" ");
+
+    // Compiling SDL with the NDK is by far the slowest step, so we only do it when the SDL sources or NDK settings have changed.
+    let fingerprint = Fingerprint::new()
+        .value(&context.config.android.sdl2_source_url)
+        .value(&context.config.android.sdl2_image_source_url)
+        .value(&context.config.android.app_platform)
+        .value(&abis)
+        .value(&format!("{:?} {}", &toolchain.ndk_dir, toolchain.version))
+        .output(&compiled_libs_dir(context));
+
+    fingerprints::run_step(context, "android-sdl2-ndk-build", &fingerprint, &mut || {
+        logs::out(log_tag!(), "Compiling SDL NDK libraries (this may take a while!) ...");
+        io::delete(context, &compiled_libs_dir(context))?;
+
+        scripts::run(
+            context,
+            &Script::new(&format!(
+                r#"{:?} NDK_PROJECT_PATH={:?} APP_BUILD_SCRIPT={:?} APP_PLATFORM={} APP_STL=c++_shared APP_ABI="{}""#,
+                &toolchain.ndk_dir.join("ndk-build"),
+                &ndk_project_dir,
+                &ndk_project_dir.join("Android.mk"),
+                &context.config.android.app_platform,
+                &abis,
+            ))
+            .working_dir(&ndk_project_dir)
+            .log_file(&scripts::log_file(context, "ndk-build")),
+        )
+    })
 }
 
 fn setup_assets(context: &Context) -> FailableUnit {
     let app_assets_dir = context.target_home_dir.join("app").join("src").join("main").join("assets");
-    let app_assets_symlink_dir = app_assets_dir.join("assets");
 
-    io::create_dir(&app_assets_dir)?;
-    io::create_symlink(&context.assets_dir, &app_assets_symlink_dir, &context.target_home_dir)?;
+    // Older builds linked the whole 'assets' directory in here, which would otherwise end up in the APK next to the pack.
+    io::delete(context, &app_assets_dir.join("assets"))?;
 
-    Ok(())
+    io::create_dir(context, &app_assets_dir)?;
+    io::create_symlink(
+        context,
+        &assets::pack_path(context),
+        &app_assets_dir.join(assets::PACK_FILE_NAME),
+        &context.target_home_dir,
+    )?;
+
+    // The library is named after the selected crate. Gradle configures the project before its 'rustBuild' task runs us, so
+    // rather than a build config field the name travels as an asset, which is merged after that task in every build.
+    io::write_string(context, &context.package.library()?.name, &app_assets_dir.join(RUST_LIBRARY_ASSET))
 }
 
 fn setup_cargo_manifest(context: &Context) -> FailableUnit {
@@ -102,92 +284,109 @@ fn setup_cargo_manifest(context: &Context) -> FailableUnit {
     manifests::create(context, "cdylib")
 }
 
-fn compile_rust_code(context: &Context, ndk_dir: &PathBuf) -> FailableUnit {
-    let is_windows = cfg!(target_os = "windows");
-
-    let ndk_toolchain_dir = ndk_dir
-        .join("toolchains")
-        .join("llvm")
-        .join("prebuilt")
-        .join(if is_windows { "windows-x86_64" } else { "darwin-x86_64" })
-        .join("bin");
-
-    logs::out(log_tag!(), &format!("Using NDK toolchain at: {:?}", &ndk_toolchain_dir));
-
-    for architecture in &vec![
-        Architecture::ARMv8A,
-        Architecture::ARMv7A,
-        Architecture::X86,
-        Architecture::X86_64,
-    ] {
-        let rust_triple = architecture.rust_triple();
-        let ndk_triple = architecture.ndk_triple();
-        let cargo_rust_triple = rust_triple.to_uppercase().replace("-", "_");
-
-        logs::out(log_tag!(), &format!("Compiling architecture: {:?}", &rust_triple));
-
-        let mut environment = HashMap::new();
-
-        environment.insert(
-            format!("CARGO_TARGET_{}_AR", &cargo_rust_triple),
-            ndk_toolchain_dir
-                .join(if is_windows {
-                    format!(r"{}-ar.exe", ndk_triple)
-                } else {
-                    format!(r"{}-ar", ndk_triple)
-                })
-                .display()
-                .to_string(),
-        );
+fn compile_rust_code(context: &Context, toolchain: &Toolchain, architectures: &[Architecture]) -> FailableUnit {
+    logs::out(log_tag!(), &format!("Using NDK toolchain at: {:?}", &toolchain.bin_dir));
 
-        environment.insert(
-            format!("CARGO_TARGET_{}_LINKER", &cargo_rust_triple),
-            ndk_toolchain_dir
-                .join(if is_windows {
-                    format!(r"{}30-clang.cmd", ndk_triple)
-                } else {
-                    format!(r"{}30-clang", ndk_triple)
-                })
-                .display()
-                .to_string(),
-        );
+    for architecture in architectures {
+        // A one line change in our Rust code only needs a recompile, but there is no need to touch any architecture whose inputs are the same as last time.
+        // Each profile compiles into its own directory and so keeps its own fingerprint, switching profiles never forces a recompile.
+        let compiled_so_path = compiled_so_path(context, architecture)?;
+        let fingerprint = rust_sources_fingerprint(context)?
+            .value(&context.cargo_build_flags())
+            .value(&format!("{:?}", &context.config.android))
+            .value(&format!("{:?} {}", &toolchain.bin_dir, toolchain.version))
+            .value(&versions::resolve(context)?.to_string())
+            .output(&compiled_so_path);
 
-        environment.insert(
-            format!("CARGO_TARGET_{}_RUSTFLAGS", &cargo_rust_triple),
-            format!(
-                "-Clink-arg=-L{} -lc++_shared -lhidapi -lSDL2 -lSDL2_image",
-                &compiled_libs_dir(context).join(architecture.jni_name()).display().to_string()
-            ),
-        );
+        let step = format!("android-compile-{}-{}", context.profile.id(), architecture.jni_name());
 
-        scripts::run(
-            &Script::new(&format!(
-                "cargo rustc {} --target-dir {:?} --lib --target {}",
-                context.variant.rust_compiler_flag(),
-                context.rust_build_dir,
-                rust_triple,
-            ))
-            .environment(&environment)
-            .working_dir(&context.working_dir),
-        )?;
+        fingerprints::run_step(context, &step, &fingerprint, &mut || compile_architecture(context, toolchain, architecture))?;
 
-        let compiled_crust_so_path = context.rust_build_dir.join(rust_triple).join(context.variant.id()).join(CRUST_SO_FILE_NAME);
+        // The libraries directory is shared by all profiles so the copy always happens, it is cheap compared to a compile.
+        let jni_so_path = compiled_libs_dir(context).join(architecture.jni_name()).join(so_file_name(context)?);
+        io::copy(context, &compiled_so_path, &jni_so_path)?;
 
-        if context.variant == Variant::Release {
-            logs::out(log_tag!(), &format!("Stripping .so library: {:?}", &compiled_crust_so_path));
-            let strip_triple = architecture.strip_triple();
-            let strip_tool = ndk_toolchain_dir.join(if is_windows {
-                format!(r"{}-strip.exe", strip_triple)
-            } else {
-                format!(r"{}-strip", strip_triple)
-            });
+        // Without '--assemble' there is nothing in the output directory, so the compiled libraries are what the report tracks.
+        build_report::record_artifact(context, &jni_so_path);
+    }
 
-            scripts::run(
-                &Script::new(&format!("{:?} {:?}", &strip_tool, &compiled_crust_so_path)).working_dir(&context.working_dir),
-            )?;
-        }
+    Ok(())
+}
 
-        io::copy(&compiled_crust_so_path, &compiled_libs_dir(context).join(architecture.jni_name()).join(CRUST_SO_FILE_NAME))?;
+// Everything Cargo itself would look at to decide whether the library needs rebuilding: the generated manifest and its lock
+// file, our sources and build script, and the manifest, sources and build script of every path dependency.
+fn rust_sources_fingerprint(context: &Context) -> Failable<Fingerprint> {
+    let manifest_path = context.working_dir.join("Cargo.toml");
+    let mut fingerprint = Fingerprint::new()
+        .file(&manifest_path)?
+        .file(&context.working_dir.join("Cargo.lock"))?
+        .file(&context.source_dir.join("build.rs"))?
+        .dir(&context.source_dir.join("src"))?;
+
+    for dependency_dir in manifests::path_dependencies(&manifest_path)? {
+        fingerprint = fingerprint
+            .file(&dependency_dir.join("Cargo.toml"))?
+            .file(&dependency_dir.join("build.rs"))?
+            .dir(&dependency_dir.join("src"))?;
+    }
+
+    Ok(fingerprint)
+}
+
+fn compiled_so_path(context: &Context, architecture: &Architecture) -> Failable<PathBuf> {
+    Ok(context.rust_build_dir.join(architecture.rust_triple()).join(context.profile.id()).join(so_file_name(context)?))
+}
+
+fn compile_architecture(context: &Context, toolchain: &Toolchain, architecture: &Architecture) -> FailableUnit {
+    let rust_triple = architecture.rust_triple();
+    let cargo_rust_triple = rust_triple.to_uppercase().replace("-", "_");
+
+    logs::out(log_tag!(), &format!("Compiling architecture: {:?}", &rust_triple));
+
+    let mut environment = HashMap::new();
+
+    environment.insert(
+        format!("CARGO_TARGET_{}_AR", &cargo_rust_triple),
+        toolchain.archiver(&architecture.binutils_triple())?.display().to_string(),
+    );
+
+    environment.insert(
+        format!("CARGO_TARGET_{}_LINKER", &cargo_rust_triple),
+        toolchain.linker(&architecture.ndk_triple(), architecture.is_64_bit())?.display().to_string(),
+    );
+
+    environment.insert(
+        format!("CARGO_TARGET_{}_RUSTFLAGS", &cargo_rust_triple),
+        format!(
+            "-Clink-arg=-L{} -lc++_shared -lhidapi -lSDL2 -lSDL2_image",
+            &compiled_libs_dir(context).join(architecture.jni_name()).display().to_string()
+        ),
+    );
+
+    scripts::run(
+        context,
+        &Script::new(&format!(
+            "cargo rustc {} --target-dir {:?} --lib --target {}",
+            context.cargo_build_flags(),
+            context.rust_build_dir,
+            rust_triple,
+        ))
+        .environment(&environment)
+        .working_dir(&context.working_dir)
+        .log_file(&scripts::log_file(context, &format!("compile-{}", rust_triple))),
+    )?;
+
+    let compiled_crust_so_path = compiled_so_path(context, architecture)?;
+
+    // Profiles which ask for debug info, such as a release build for profiling, keep their symbols.
+    if !context.profile.debug_info {
+        logs::out(log_tag!(), &format!("Stripping .so library: {:?}", &compiled_crust_so_path));
+        let strip_tool = toolchain.strip(&architecture.binutils_triple())?;
+
+        scripts::run(
+            context,
+            &Script::new(&format!("{:?} {:?}", &strip_tool, &compiled_crust_so_path)).working_dir(&context.working_dir),
+        )?;
     }
 
     Ok(())
@@ -197,7 +396,7 @@ fn link_jni_libs(context: &Context) -> FailableUnit {
     let app_jni_libs_dir = context.target_home_dir.join("app").join("src").join("main").join("jniLibs");
 
     logs::out(log_tag!(), "Linking 'libs' into Android app 'jniLibs' ...");
-    io::create_symlink(&compiled_libs_dir(context), &app_jni_libs_dir, &context.target_home_dir)?;
+    io::create_symlink(context, &compiled_libs_dir(context), &app_jni_libs_dir, &context.target_home_dir)?;
 
     Ok(())
 }
@@ -210,6 +409,19 @@ enum Architecture {
 }
 
 impl Architecture {
+    fn resolve(jni_name: &str) -> Failable<Architecture> {
+        match jni_name {
+            "arm64-v8a" => Ok(Architecture::ARMv8A),
+            "armeabi-v7a" => Ok(Architecture::ARMv7A),
+            "x86" => Ok(Architecture::X86),
+            "x86_64" => Ok(Architecture::X86_64),
+            _ => {
+                Err(format!("Unknown Android architecture {:?}, expected one of: arm64-v8a, armeabi-v7a, x86, x86_64", jni_name)
+                    .into())
+            }
+        }
+    }
+
     fn jni_name(&self) -> String {
         String::from(match self {
             Architecture::ARMv8A => "arm64-v8a",
@@ -237,7 +449,12 @@ impl Architecture {
         })
     }
 
-    fn strip_triple(&self) -> String {
+    fn is_64_bit(&self) -> bool {
+        matches!(self, Architecture::ARMv8A | Architecture::X86_64)
+    }
+
+    // Prefix of the GNU binutils such as 'ar' and 'strip' in NDKs older than r23.
+    fn binutils_triple(&self) -> String {
         String::from(match self {
             Architecture::ARMv8A => "aarch64-linux-android",
             Architecture::ARMv7A => "arm-linux-androideabi",
@@ -0,0 +1,239 @@
+use crate::{
+    core::{context::Context, failable::Failable, io, logs},
+    log_tag,
+};
+use std::path::PathBuf;
+
+// NDK r19 was the first release to ship the standalone style '<triple><api>-clang' wrappers in its prebuilt LLVM toolchain.
+const MIN_SUPPORTED_MAJOR_VERSION: u32 = 19;
+
+// NDK r23 removed the GNU binutils, leaving only the LLVM equivalents such as 'llvm-ar' and 'llvm-strip'.
+const LLVM_BINUTILS_MAJOR_VERSION: u32 = 23;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub build: u32,
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "r{} ({}.{}.{})", self.major, self.major, self.minor, self.build)
+    }
+}
+
+// Planned against when a dry run happens on a machine without an NDK, any recent NDK gives the same shape of build.
+const PLACEHOLDER_REVISION: &str = "25.0.0";
+
+pub struct Toolchain {
+    pub ndk_dir: PathBuf,
+    pub version: Version,
+    pub bin_dir: PathBuf,
+    // The API level from the config, without one each architecture is linked against the oldest API level the NDK supports for it.
+    pub api_level: Option<u32>,
+    // A toolchain that is only planned against describes where the tools would be, nothing about it is checked on disk.
+    pub is_planned: bool,
+}
+
+pub fn ndk_dir() -> Failable<PathBuf> {
+    let ndk_dir = match std::env::var("ANDROID_NDK_ROOT") {
+        Ok(ndk_dir) => PathBuf::from(ndk_dir),
+        _ => {
+            return Err(
+                "The ANDROID_NDK_ROOT environment variable must be set to the directory of an installed Android NDK.".into()
+            )
+        }
+    };
+
+    if !ndk_dir.is_dir() {
+        return Err(format!("ANDROID_NDK_ROOT {:?} is not a directory.", &ndk_dir).into());
+    }
+
+    Ok(ndk_dir)
+}
+
+pub fn read_version(ndk_dir: &PathBuf) -> Failable<Version> {
+    let properties_file = ndk_dir.join("source.properties");
+
+    if !properties_file.exists() {
+        return Err(format!(
+            "No 'source.properties' found in {:?}, is ANDROID_NDK_ROOT pointing at the root of an NDK?",
+            ndk_dir
+        )
+        .into());
+    }
+
+    // The version is declared as 'Pkg.Revision = <major>.<minor>.<build>', optionally followed by a suffix such as '-beta1'.
+    let revision = io::read_string(&properties_file)?
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(key, _)| key.trim() == "Pkg.Revision")
+        .map(|(_, value)| value.trim().to_owned())
+        .ok_or_else(|| format!("No 'Pkg.Revision' entry in {:?}", &properties_file))?;
+
+    parse_revision(&revision).ok_or_else(|| format!("Unrecognised NDK revision {:?} in {:?}", &revision, &properties_file).into())
+}
+
+fn parse_revision(revision: &str) -> Option<Version> {
+    let numbers: Vec<u32> =
+        revision.split(|c: char| !c.is_ascii_digit()).take(3).map(|number| number.parse().unwrap_or(0)).collect();
+
+    match numbers.as_slice() {
+        [major, minor, build] if *major > 0 => Some(Version {
+            major: *major,
+            minor: *minor,
+            build: *build,
+        }),
+        _ => None,
+    }
+}
+
+// The NDK is found through a probe answered as '<ndk dir>\n<revision>', so a dry run or a test can plan an Android build
+// without an NDK being installed. Only a real build resolves (and so checks) the toolchain on disk.
+pub fn locate(context: &Context, api_level: Option<u32>) -> Failable<Toolchain> {
+    if !context.executor.is_recording() {
+        return resolve(&ndk_dir()?, api_level);
+    }
+
+    let answer = context.executor.probe("android-ndk", &mut || {
+        let ndk_dir = ndk_dir().ok()?;
+        let version = read_version(&ndk_dir).ok()?;
+        Some(format!("{}\n{}.{}.{}", ndk_dir.display(), version.major, version.minor, version.build))
+    });
+
+    let (ndk_dir, revision) = match answer.as_ref().and_then(|answer| answer.split_once('\n')) {
+        Some((ndk_dir, revision)) => (PathBuf::from(ndk_dir), revision),
+        None => {
+            logs::out(log_tag!(), "No Android NDK found, planning against a placeholder NDK in '$ANDROID_NDK_ROOT'.");
+            (PathBuf::from("$ANDROID_NDK_ROOT"), PLACEHOLDER_REVISION)
+        }
+    };
+
+    Ok(Toolchain {
+        bin_dir: ndk_dir.join("toolchains").join("llvm").join("prebuilt").join(host_tag()?).join("bin"),
+        ndk_dir,
+        version: parse_revision(revision).ok_or_else(|| format!("Unrecognised NDK revision {:?}", revision))?,
+        api_level,
+        is_planned: true,
+    })
+}
+
+pub fn host_tag() -> Failable<&'static str> {
+    // The NDK only ships x86_64 host toolchains, Apple Silicon Macs run the 'darwin-x86_64' one under Rosetta.
+    if cfg!(target_os = "windows") && cfg!(target_arch = "x86_64") {
+        Ok("windows-x86_64")
+    } else if cfg!(target_os = "linux") && cfg!(target_arch = "x86_64") {
+        Ok("linux-x86_64")
+    } else if cfg!(target_os = "macos") {
+        Ok("darwin-x86_64")
+    } else {
+        Err(format!(
+            "The Android NDK has no prebuilt toolchain for this host ({} {}).",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )
+        .into())
+    }
+}
+
+pub fn min_api_level(version: &Version, is_64_bit: bool) -> u32 {
+    // 64 bit ABIs only exist from Android 5.0 onwards, and newer NDKs progressively dropped support for old platforms.
+    if is_64_bit || version.major >= 26 {
+        21
+    } else if version.major >= 24 {
+        19
+    } else {
+        16
+    }
+}
+
+pub fn resolve(ndk_dir: &PathBuf, api_level: Option<u32>) -> Failable<Toolchain> {
+    let version = read_version(ndk_dir)?;
+
+    if version.major < MIN_SUPPORTED_MAJOR_VERSION {
+        return Err(format!(
+            "Android NDK {} in {:?} is not supported, at least r{} is required.",
+            version, ndk_dir, MIN_SUPPORTED_MAJOR_VERSION
+        )
+        .into());
+    }
+
+    let host_tag = host_tag()?;
+    let bin_dir = ndk_dir.join("toolchains").join("llvm").join("prebuilt").join(host_tag).join("bin");
+
+    if !bin_dir.is_dir() {
+        return Err(format!("Android NDK {} has no '{}' prebuilt toolchain, expected {:?}", version, host_tag, &bin_dir).into());
+    }
+
+    Ok(Toolchain {
+        ndk_dir: ndk_dir.clone(),
+        version,
+        bin_dir,
+        api_level,
+        is_planned: false,
+    })
+}
+
+impl Toolchain {
+    pub fn uses_llvm_binutils(&self) -> bool {
+        self.version.major >= LLVM_BINUTILS_MAJOR_VERSION
+    }
+
+    pub fn linker(&self, clang_triple: &str, is_64_bit: bool) -> Failable<PathBuf> {
+        let min_api_level = min_api_level(&self.version, is_64_bit);
+        let api_level = self.api_level.unwrap_or(min_api_level);
+
+        if api_level < min_api_level {
+            return Err(format!(
+                "Android NDK {} requires an API level of at least {} for '{}', but 'ndk_api_level' is {}.",
+                self.version, min_api_level, clang_triple, api_level
+            )
+            .into());
+        }
+
+        let linker = self.bin_dir.join(if cfg!(target_os = "windows") {
+            format!("{}{}-clang.cmd", clang_triple, api_level)
+        } else {
+            format!("{}{}-clang", clang_triple, api_level)
+        });
+
+        if !self.is_planned && !linker.exists() {
+            return Err(format!(
+                "Android NDK {} has no clang for '{}' at API level {}, expected {:?}",
+                self.version, clang_triple, api_level, &linker
+            )
+            .into());
+        }
+
+        Ok(linker)
+    }
+
+    pub fn archiver(&self, binutils_triple: &str) -> Failable<PathBuf> {
+        self.binutil(binutils_triple, "ar")
+    }
+
+    pub fn strip(&self, binutils_triple: &str) -> Failable<PathBuf> {
+        self.binutil(binutils_triple, "strip")
+    }
+
+    fn binutil(&self, binutils_triple: &str, name: &str) -> Failable<PathBuf> {
+        let file_name = if self.uses_llvm_binutils() {
+            format!("llvm-{}", name)
+        } else {
+            format!("{}-{}", binutils_triple, name)
+        };
+
+        let tool = self.bin_dir.join(if cfg!(target_os = "windows") {
+            format!("{}.exe", file_name)
+        } else {
+            file_name
+        });
+
+        if !self.is_planned && !tool.exists() {
+            return Err(format!("Android NDK {} is missing the '{}' tool, expected {:?}", self.version, name, &tool).into());
+        }
+
+        Ok(tool)
+    }
+}
@@ -0,0 +1,34 @@
+use crate::core::{context::Context, failable_unit::FailableUnit, io};
+use std::path::PathBuf;
+
+// The single file asset pack mounted by 'crust-main/src/core/vfs.rs', every number is little endian:
+// "CPAK", u32 version, u32 entry count, then per entry a u32 path length, the UTF-8 path, a u64 offset from the start of
+// the pack and a u64 size, followed by the content of every entry in the same order.
+const MAGIC: &[u8; 4] = b"CPAK";
+const VERSION: u32 = 1;
+
+// Each entry is the path the application will ask for and the file holding its content.
+pub fn write(context: &Context, entries: &[(String, PathBuf)], pack_path: &PathBuf) -> FailableUnit {
+    let index_size: usize = 12 + entries.iter().map(|(path, _)| 4 + path.len() + 16).sum::<usize>();
+
+    let mut index = Vec::with_capacity(index_size);
+    let mut content = Vec::new();
+
+    index.extend_from_slice(MAGIC);
+    index.extend_from_slice(&VERSION.to_le_bytes());
+    index.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+
+    for (path, file) in entries {
+        let data = std::fs::read(file)?;
+
+        index.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        index.extend_from_slice(path.as_bytes());
+        index.extend_from_slice(&((index_size + content.len()) as u64).to_le_bytes());
+        index.extend_from_slice(&(data.len() as u64).to_le_bytes());
+
+        content.extend_from_slice(&data);
+    }
+
+    index.extend_from_slice(&content);
+    io::write_bytes(context, &index, pack_path)
+}
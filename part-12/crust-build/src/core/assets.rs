@@ -0,0 +1,218 @@
+use crate::{
+    core::{
+        asset_pack, context::Context, failable::Failable, failable_unit::FailableUnit, fingerprint::Fingerprint, fingerprints,
+        hashes, io, logs, meshes, shaders, textures,
+    },
+    log_tag,
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+const MANIFEST_FILE_NAME: &str = "asset-manifest.json";
+
+// The pack holds every processed asset under the same 'assets/...' path the application loads it by.
+pub const PACK_FILE_NAME: &str = "assets.pack";
+const PACK_ROOT: &str = "assets";
+
+// Part of every cache key, bump it whenever a converter changes what it produces so stale cache entries are never used.
+const PIPELINE_VERSION: &str = "1";
+
+#[derive(Serialize)]
+struct AssetManifest {
+    texture_format: String,
+    assets: Vec<AssetEntry>,
+}
+
+#[derive(Serialize)]
+struct AssetEntry {
+    path: String,
+    source: String,
+    kind: String,
+    size: u64,
+    sha256: String,
+}
+
+enum Step {
+    Shader,
+    Mesh,
+    Texture,
+    Copy,
+}
+
+impl Step {
+    fn of(path: &PathBuf) -> Step {
+        if shaders::is_shader(path) {
+            Step::Shader
+        } else if meshes::is_mesh_source(path) {
+            Step::Mesh
+        } else if textures::is_texture_source(path) {
+            Step::Texture
+        } else {
+            Step::Copy
+        }
+    }
+
+    fn kind(&self) -> &str {
+        match self {
+            Step::Shader => "shader",
+            Step::Mesh => "mesh",
+            Step::Texture => "texture",
+            Step::Copy => "file",
+        }
+    }
+}
+
+// The processed assets as loose files, which the pack is built from and which are handy for inspecting what was produced.
+pub fn output_dir(context: &Context) -> PathBuf {
+    context.working_dir.join("assets")
+}
+
+// What the targets ship instead of the 'assets' directory, the application mounts it by this file name.
+pub fn pack_path(context: &Context) -> PathBuf {
+    context.working_dir.join(PACK_FILE_NAME)
+}
+
+fn cache_dir(context: &Context) -> PathBuf {
+    context.working_dir.join("asset-cache")
+}
+
+pub fn process(context: &Context) -> FailableUnit {
+    let output_dir = output_dir(context);
+    let config = &context.config.assets;
+    let validate_shaders = shaders::should_validate(context);
+
+    let fingerprint = Fingerprint::new()
+        .value(PIPELINE_VERSION)
+        .value(config.texture_format.id())
+        .value(&validate_shaders.to_string())
+        .dir(&context.assets_dir)?
+        .output(&output_dir.join(MANIFEST_FILE_NAME))
+        .output(&pack_path(context));
+
+    fingerprints::run_step(context, "assets", &fingerprint, &mut || {
+        context.executor.file_operation("process_assets", Some(&context.assets_dir), &pack_path(context), &mut || {
+            process_all(context, &output_dir, validate_shaders)
+        })
+    })
+}
+
+fn process_all(context: &Context, output_dir: &PathBuf, validate_shaders: bool) -> FailableUnit {
+    logs::out(log_tag!(), "Processing assets ...");
+
+    io::delete(context, output_dir)?;
+    io::create_dir(context, output_dir)?;
+
+    let mut entries = Vec::new();
+    let mut cached_count = 0;
+
+    for source in io::list_files(&context.assets_dir)? {
+        let relative = source.strip_prefix(&context.assets_dir)?;
+
+        // Files like '.DS_Store' or editor swap files are never part of what gets shipped.
+        if relative.components().any(|component| component.as_os_str().to_string_lossy().starts_with('.')) {
+            continue;
+        }
+
+        let step = Step::of(&source);
+        let destination_relative = match step {
+            Step::Mesh => relative.with_extension("mesh"),
+            Step::Texture => relative.with_extension("texture"),
+            Step::Shader | Step::Copy => relative.to_path_buf(),
+        };
+        let destination = output_dir.join(&destination_relative);
+        io::create_dir(context, &destination.parent().ok_or("Parent directory not found")?.to_path_buf())?;
+
+        match step {
+            Step::Copy => io::copy(context, &source, &destination)?,
+            _ => {
+                let cached_path = cached_path(context, &step, &source, validate_shaders)?;
+
+                if cached_path.exists() {
+                    cached_count += 1;
+                } else {
+                    let processed = run(context, &step, &source, validate_shaders)?;
+                    let partial_path = cached_path.with_extension("partial");
+                    io::write_bytes(context, &processed, &partial_path)?;
+                    io::rename(context, &partial_path, &cached_path)?;
+                }
+
+                io::copy(context, &cached_path, &destination)?;
+            }
+        }
+
+        entries.push(AssetEntry {
+            path: portable_path(&destination_relative),
+            source: portable_path(relative),
+            kind: step.kind().to_owned(),
+            size: std::fs::metadata(&destination)?.len(),
+            sha256: hashes::sha256_file(&destination)?,
+        });
+    }
+
+    let manifest = AssetManifest {
+        texture_format: context.config.assets.texture_format.id().to_owned(),
+        assets: entries,
+    };
+    io::write_string(context, &serde_json::to_string_pretty(&manifest)?, &output_dir.join(MANIFEST_FILE_NAME))?;
+
+    let mut pack_entries: Vec<(String, PathBuf)> =
+        manifest.assets.iter().map(|asset| (format!("{}/{}", PACK_ROOT, asset.path), output_dir.join(&asset.path))).collect();
+    pack_entries.push((format!("{}/{}", PACK_ROOT, MANIFEST_FILE_NAME), output_dir.join(MANIFEST_FILE_NAME)));
+    asset_pack::write(context, &pack_entries, &pack_path(context))?;
+
+    logs::out(
+        log_tag!(),
+        &format!(
+            "Processed {} assets into {:?}, {} taken from the cache.",
+            manifest.assets.len(),
+            pack_path(context),
+            cached_count
+        ),
+    );
+
+    Ok(())
+}
+
+// Keyed by the content of the source and everything that changes how it is processed, so renaming or moving an asset, or
+// switching targets back and forth, never processes the same content twice.
+fn cached_path(context: &Context, step: &Step, source: &PathBuf, validate_shaders: bool) -> Failable<PathBuf> {
+    let settings = match step {
+        Step::Shader => format!("validate-{}", validate_shaders),
+        Step::Texture => context.config.assets.texture_format.id().to_owned(),
+        Step::Mesh | Step::Copy => String::new(),
+    };
+
+    let key = hashes::sha256_bytes(
+        format!("{}:{}:{}:{}", PIPELINE_VERSION, step.kind(), settings, hashes::sha256_file(source)?).as_bytes(),
+    );
+
+    Ok(cache_dir(context).join(key))
+}
+
+fn run(context: &Context, step: &Step, source: &PathBuf, validate_shaders: bool) -> Failable<Vec<u8>> {
+    let content = std::fs::read(source)?;
+
+    match step {
+        Step::Shader => {
+            if validate_shaders {
+                logs::out(log_tag!(), &format!("Validating shader {:?} ...", source));
+                shaders::validate(context, source, &String::from_utf8_lossy(&content))?;
+            }
+            Ok(content)
+        }
+        Step::Mesh => {
+            logs::out(log_tag!(), &format!("Converting model {:?} ...", source));
+            meshes::convert_obj(source, &content)
+        }
+        Step::Texture => {
+            logs::out(log_tag!(), &format!("Converting texture {:?} ...", source));
+            textures::convert_png(source, &content, context.config.assets.texture_format)
+        }
+        Step::Copy => Ok(content),
+    }
+}
+
+// Manifest paths use '/' on every host so the manifest is the same wherever the build ran.
+fn portable_path(path: &Path) -> String {
+    path.components().map(|component| component.as_os_str().to_string_lossy().to_string()).collect::<Vec<_>>().join("/")
+}
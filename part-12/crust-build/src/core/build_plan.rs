@@ -0,0 +1,169 @@
+use crate::core::{executor::Executor, failable::Failable, failable_unit::FailableUnit, script::Script, scripts::ScriptOutput};
+use serde::Serialize;
+use std::{cell::RefCell, collections::BTreeMap, fmt::Write, path::PathBuf};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Step {
+    Script {
+        content: String,
+        working_dir: Option<PathBuf>,
+        environment: BTreeMap<String, String>,
+    },
+    FileOperation {
+        operation: String,
+        source: Option<PathBuf>,
+        destination: PathBuf,
+    },
+    Download {
+        url: String,
+        destination: PathBuf,
+    },
+    Probe {
+        name: String,
+        answer: Option<String>,
+    },
+}
+
+// Records the steps of a build instead of running them. Probes are answered from the given answers first, then by asking the
+// host if 'probe_host' is set, so a dry run still reflects the machine it runs on while tests can pin every answer down.
+pub struct RecordingExecutor {
+    answers: BTreeMap<String, String>,
+    probe_host: bool,
+    steps: RefCell<Vec<Step>>,
+}
+
+impl RecordingExecutor {
+    pub fn new(answers: BTreeMap<String, String>, probe_host: bool) -> Self {
+        RecordingExecutor {
+            answers,
+            probe_host,
+            steps: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn steps(&self) -> Vec<Step> {
+        self.steps.borrow().clone()
+    }
+
+    fn record(&self, step: Step) {
+        self.steps.borrow_mut().push(step);
+    }
+}
+
+impl Executor for RecordingExecutor {
+    fn run_script(&self, script: &Script, _: bool) -> Failable<ScriptOutput> {
+        self.record(Step::Script {
+            content: script.content.clone(),
+            working_dir: script.working_dir.clone(),
+            environment: script.environment.clone().into_iter().collect(),
+        });
+
+        Ok(ScriptOutput::default())
+    }
+
+    fn file_operation(
+        &self,
+        operation: &str,
+        source: Option<&PathBuf>,
+        destination: &PathBuf,
+        _: &mut dyn FnMut() -> FailableUnit,
+    ) -> FailableUnit {
+        self.record(Step::FileOperation {
+            operation: operation.to_owned(),
+            source: source.cloned(),
+            destination: destination.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn download(&self, url: &str, destination: &PathBuf, _: &mut dyn FnMut() -> FailableUnit) -> FailableUnit {
+        self.record(Step::Download {
+            url: url.to_owned(),
+            destination: destination.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn probe(&self, name: &str, query: &mut dyn FnMut() -> Option<String>) -> Option<String> {
+        let answer = match self.answers.get(name) {
+            Some(answer) => Some(answer.clone()),
+            None if self.probe_host => query(),
+            None => None,
+        };
+
+        self.record(Step::Probe {
+            name: name.to_owned(),
+            answer: answer.clone(),
+        });
+
+        answer
+    }
+
+    fn is_recording(&self) -> bool {
+        true
+    }
+}
+
+pub fn to_json(steps: &[Step]) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(steps)
+}
+
+pub fn print(steps: &[Step]) {
+    println!("---------------------------------------------");
+    println!("Build plan ({} steps):", steps.len());
+    print!("{}", render(steps));
+    println!("---------------------------------------------");
+}
+
+// A dry run prints this, and the plan tests compare against it.
+pub fn render(steps: &[Step]) -> String {
+    let mut output = String::new();
+
+    for (index, step) in steps.iter().enumerate() {
+        // Writing into a String can't fail.
+        let _ = match step {
+            Step::Script {
+                content,
+                working_dir,
+                environment,
+            } => {
+                let _ = writeln!(output, "[{}] script", index + 1);
+
+                if let Some(working_dir) = working_dir {
+                    let _ = writeln!(output, "    working dir: {:?}", working_dir);
+                }
+
+                for (key, value) in environment {
+                    let _ = writeln!(output, "    env: {}={}", key, value);
+                }
+
+                for line in content.lines() {
+                    let _ = writeln!(output, "    > {}", line);
+                }
+
+                Ok(())
+            }
+
+            Step::FileOperation {
+                operation,
+                source: Some(source),
+                destination,
+            } => writeln!(output, "[{}] {}: {:?} => {:?}", index + 1, operation, source, destination),
+
+            Step::FileOperation {
+                operation,
+                source: None,
+                destination,
+            } => writeln!(output, "[{}] {}: {:?}", index + 1, operation, destination),
+
+            Step::Download { url, destination } => writeln!(output, "[{}] download: {} => {:?}", index + 1, url, destination),
+
+            Step::Probe { name, answer } => writeln!(output, "[{}] probe: {} => {:?}", index + 1, name, answer),
+        };
+    }
+
+    output
+}
@@ -0,0 +1,326 @@
+use crate::{
+    core::{
+        context::Context, executor, failable::Failable, failable_unit::FailableUnit, hashes, io, logs, outputs, script::Script,
+        versions::Version,
+    },
+    log_tag,
+};
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+pub const REPORT_FILE_NAME: &str = "build-report.json";
+
+// Only these contribute to the size delta, everything else in the output (assets, scripts etc) is listed but not tracked for bloat.
+const BINARY_EXTENSIONS: &[&str] = &["wasm", "so", "exe", "dll", "dylib"];
+
+// Held by the context, while a build is running the report is filled in by the steps and scripts as they execute.
+#[derive(Default)]
+pub struct BuildReport {
+    recording: RefCell<Option<Recording>>,
+}
+
+struct Recording {
+    version: String,
+    started: Instant,
+    started_at: Duration,
+    steps: Vec<StepReport>,
+    scripts: Vec<ScriptReport>,
+    third_party: BTreeMap<String, ThirdPartyReport>,
+    artifacts: Vec<PathBuf>,
+    previous_sizes: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize)]
+struct Report {
+    target: String,
+    variant: String,
+    profile: String,
+    version: String,
+    success: bool,
+    error: Option<String>,
+    started_at: u64,
+    duration_ms: u128,
+    paths: BTreeMap<String, PathBuf>,
+    third_party: BTreeMap<String, ThirdPartyReport>,
+    steps: Vec<StepReport>,
+    scripts: Vec<ScriptReport>,
+    artifacts: Vec<ArtifactReport>,
+}
+
+#[derive(Serialize)]
+struct StepReport {
+    name: String,
+    skipped: bool,
+    duration_ms: u128,
+}
+
+#[derive(Serialize)]
+struct ScriptReport {
+    content: String,
+    working_dir: Option<PathBuf>,
+    exit_status: Option<i32>,
+    duration_ms: u128,
+}
+
+#[derive(Serialize, Clone)]
+struct ThirdPartyReport {
+    version: String,
+    source: String,
+}
+
+#[derive(Serialize)]
+struct ArtifactReport {
+    path: String,
+    size: u64,
+    sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size_delta: Option<i64>,
+}
+
+pub fn start(context: &Context, version: &Version) {
+    // The previous report lives in the output directory, which the build itself cleans, so it has to be read before anything runs.
+    let previous_sizes = previous_binary_sizes(&outputs::output_dir(context).join(REPORT_FILE_NAME));
+
+    *context.build_report.recording.borrow_mut() = Some(Recording {
+        version: version.to_string(),
+        started: Instant::now(),
+        started_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default(),
+        steps: Vec::new(),
+        scripts: Vec::new(),
+        third_party: BTreeMap::new(),
+        artifacts: Vec::new(),
+        previous_sizes,
+    });
+}
+
+pub fn record_step(context: &Context, name: &str, skipped: bool, duration: Duration) {
+    record(context, |recording| {
+        recording.steps.push(StepReport {
+            name: name.to_owned(),
+            skipped,
+            duration_ms: duration.as_millis(),
+        })
+    });
+}
+
+pub fn record_script(context: &Context, script: &Script, exit_status: Option<i32>, duration: Duration) {
+    record(context, |recording| {
+        recording.scripts.push(ScriptReport {
+            content: script.content.clone(),
+            working_dir: script.working_dir.clone(),
+            exit_status,
+            duration_ms: duration.as_millis(),
+        })
+    });
+}
+
+pub fn record_third_party(context: &Context, name: &str, version: &str, source: &str) {
+    record(context, |recording| {
+        recording.third_party.insert(
+            name.to_owned(),
+            ThirdPartyReport {
+                version: version.to_owned(),
+                source: source.to_owned(),
+            },
+        );
+    });
+}
+
+#[allow(clippy::ptr_arg)]
+pub fn record_artifact(context: &Context, path: &PathBuf) {
+    record(context, |recording| recording.artifacts.push(path.clone()));
+}
+
+pub fn version_from_url(url: &str) -> String {
+    // Archive names look like 'SDL2-2.0.14.zip' or 'SDL2_image-devel-2.0.5-VC.zip', the version is the part starting with a digit.
+    let file_name = url.rsplit('/').next().unwrap_or(url);
+    let stem = [".tar.gz", ".tar.xz", ".zip", ".dmg"].iter().fold(file_name, |name, suffix| name.trim_end_matches(suffix));
+
+    stem.split('-').find(|part| part.starts_with(|c: char| c.is_ascii_digit())).unwrap_or(stem).to_owned()
+}
+
+fn record(context: &Context, update: impl FnOnce(&mut Recording)) {
+    if let Some(recording) = context.build_report.recording.borrow_mut().as_mut() {
+        update(recording);
+    }
+}
+
+pub fn finish(context: &Context, result: &FailableUnit) -> FailableUnit {
+    let recording = match context.build_report.recording.borrow_mut().take() {
+        Some(recording) => recording,
+        None => return Ok(()),
+    };
+
+    // A dry run never produces anything worth reporting on.
+    if context.executor.is_recording() {
+        return Ok(());
+    }
+
+    let output_dir = outputs::output_dir(context);
+    let report_path = output_dir.join(REPORT_FILE_NAME);
+
+    let mut third_party = recording.third_party.clone();
+    if let Some(rustc) = executor::probe_command(context, "rustc", &["--version"]) {
+        third_party.insert(
+            "rustc".to_owned(),
+            ThirdPartyReport {
+                version: rustc,
+                source: "rustc --version".to_owned(),
+            },
+        );
+    }
+
+    let mut artifact_paths = if output_dir.exists() {
+        io::list_files(&output_dir)?
+    } else {
+        Vec::new()
+    };
+    artifact_paths.retain(|path| path != &report_path);
+    artifact_paths.extend(recording.artifacts.iter().filter(|path| path.exists()).cloned());
+
+    let mut artifacts = Vec::new();
+    for path in artifact_paths {
+        artifacts.push(artifact_report(context, &path, &recording.previous_sizes)?);
+    }
+
+    let report = Report {
+        target: context.target.id().to_owned(),
+        variant: context.variant.id().to_owned(),
+        profile: context.profile.name.clone(),
+        version: recording.version,
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|error| error.to_string()),
+        started_at: recording.started_at.as_secs(),
+        duration_ms: recording.started.elapsed().as_millis(),
+        paths: context_paths(context),
+        third_party,
+        steps: recording.steps,
+        scripts: recording.scripts,
+        artifacts,
+    };
+
+    for artifact in report.artifacts.iter().filter(|artifact| is_binary(context, &artifact.path)) {
+        match artifact.size_delta {
+            Some(delta) => {
+                logs::out(log_tag!(), &format!("Binary size: {} {} bytes ({:+})", artifact.path, artifact.size, delta))
+            }
+            None => logs::out(log_tag!(), &format!("Binary size: {} {} bytes (new)", artifact.path, artifact.size)),
+        }
+    }
+
+    logs::out(log_tag!(), &format!("Writing build report: {:?}", &report_path));
+    io::create_dir(context, &output_dir)?;
+    io::write_string(context, &serde_json::to_string_pretty(&report)?, &report_path)
+}
+
+fn artifact_report(context: &Context, path: &PathBuf, previous_sizes: &BTreeMap<String, u64>) -> Failable<ArtifactReport> {
+    // Paths are relative to the target's home dir so reports from different machines or checkouts can be compared.
+    let relative_path = path
+        .strip_prefix(&context.target_home_dir)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let size = std::fs::metadata(path)?.len();
+    let previous_size = if is_binary(context, &relative_path) {
+        previous_sizes.get(&relative_path).copied()
+    } else {
+        None
+    };
+
+    Ok(ArtifactReport {
+        sha256: hashes::sha256_file(path)?,
+        size_delta: previous_size.map(|previous_size| size as i64 - previous_size as i64),
+        previous_size,
+        path: relative_path,
+        size,
+    })
+}
+
+fn is_binary(context: &Context, relative_path: &str) -> bool {
+    let file_name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+
+    file_name == context.binary_name
+        || file_name.rsplit_once('.').map_or(false, |(_, extension)| BINARY_EXTENSIONS.contains(&extension))
+}
+
+fn previous_binary_sizes(report_path: &PathBuf) -> BTreeMap<String, u64> {
+    // The previous report is read loosely, if it is missing or from an older format we simply have nothing to compare against.
+    let previous = std::fs::read_to_string(report_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .unwrap_or_default();
+
+    previous["artifacts"]
+        .as_array()
+        .map(|artifacts| {
+            artifacts
+                .iter()
+                .filter_map(|artifact| Some((artifact["path"].as_str()?.to_owned(), artifact["size"].as_u64()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn context_paths(context: &Context) -> BTreeMap<String, PathBuf> {
+    let mut paths = BTreeMap::new();
+    paths.insert("assets_dir".to_owned(), context.assets_dir.clone());
+    paths.insert("config_file".to_owned(), context.config_file.clone());
+    paths.insert("downloads_cache_dir".to_owned(), context.downloads_cache_dir.clone());
+    paths.insert("output_dir".to_owned(), outputs::output_dir(context));
+    paths.insert("rust_build_dir".to_owned(), context.rust_build_dir.clone());
+    paths.insert("source_dir".to_owned(), context.source_dir.clone());
+    paths.insert("target_home_dir".to_owned(), context.target_home_dir.clone());
+    paths.insert("working_dir".to_owned(), context.working_dir.clone());
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{context::Options, target::Target, variant::Variant};
+
+    #[test]
+    fn size_delta_survives_the_output_being_cleaned() {
+        let root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf();
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let mut context = Context::new(root_dir, Options::new(Target::Linux, Variant::Debug)).unwrap();
+        context.target_home_dir = temp_dir.path().join("linux");
+        context.working_dir = context.target_home_dir.join(".rust-build");
+
+        let output_dir = outputs::output_dir(&context);
+        let previous_report = r#"{ "artifacts": [{ "path": "out/debug/crust", "size": 10 }] }"#;
+        std::fs::create_dir_all(&output_dir).unwrap();
+        std::fs::write(output_dir.join(REPORT_FILE_NAME), previous_report).unwrap();
+
+        let version = Version {
+            version: "1.0.0".to_owned(),
+            build_number: 1,
+            git_describe: "unknown".to_owned(),
+        };
+        start(&context, &version);
+
+        // Collecting the outputs starts by cleaning the output directory, taking the previous report with it.
+        std::fs::remove_dir_all(&output_dir).unwrap();
+        std::fs::create_dir_all(&output_dir).unwrap();
+        std::fs::write(output_dir.join("crust"), [0u8; 15]).unwrap();
+
+        finish(&context, &Ok(())).unwrap();
+
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(output_dir.join(REPORT_FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(report["artifacts"][0]["path"], "out/debug/crust");
+        assert_eq!(report["artifacts"][0]["previous_size"], 10);
+        assert_eq!(report["artifacts"][0]["size_delta"], 5);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{
+    core::{
+        assets, build_report, context::Context, failable::Failable, failable_unit::FailableUnit, fingerprint::Fingerprint,
+        fingerprints, logs, outputs, packages, target::Target,
+    },
+    log_tag,
+};
+use std::{
+    cell::{Ref, RefCell},
+    path::PathBuf,
+    time::Instant,
+};
+
+// Each platform implements the phases it needs, the order they run in and the logging and timing around them live here.
+pub trait BuildTarget {
+    fn target(&self) -> Target;
+
+    fn is_supported_on_host(&self) -> bool {
+        true
+    }
+
+    // Fetch or build third party libraries, install Rust targets and set up any generated project files.
+    fn prepare_dependencies(&self, context: &Context) -> FailableUnit;
+
+    // Validates and converts the source assets into 'assets::output_dir', targets whose own project bundles the source
+    // assets directly (the Xcode based ones) have nothing to consume the result and skip it.
+    fn process_assets(&self, context: &Context) -> FailableUnit {
+        assets::process(context)
+    }
+
+    fn compile(&self, context: &Context) -> FailableUnit;
+
+    // The files and directories copied into a freshly cleaned output directory. Targets whose output is produced by the
+    // platform's own project (Gradle or Xcode) have none and do their work in 'create_output' instead.
+    fn output_files(&self, _context: &Context) -> Failable<Vec<PathBuf>> {
+        Ok(Vec::new())
+    }
+
+    // Anything besides the output files that should trigger collecting them again when it changes, such as the asset pack.
+    fn output_fingerprint(&self, _context: &Context, fingerprint: Fingerprint) -> Failable<Fingerprint> {
+        Ok(fingerprint)
+    }
+
+    // Runs after the output files were collected, to link or copy in anything else the output needs.
+    fn complete_output(&self, _context: &Context) -> FailableUnit {
+        Ok(())
+    }
+
+    // Produces output that can't simply be collected, or puts it wherever the platform's own project expects it.
+    fn create_output(&self, _context: &Context) -> FailableUnit {
+        Ok(())
+    }
+
+    // Runs against the staged copy of the output before it is archived, for example to bundle shared libraries.
+    fn prepare_package(&self, _context: &Context, _stage_dir: &PathBuf) -> FailableUnit {
+        Ok(())
+    }
+
+    // Creates any platform specific packages once the archives have been written.
+    fn package(&self, _context: &Context, _stage_dir: &PathBuf) -> FailableUnit {
+        Ok(())
+    }
+
+    fn run(&self, context: &Context) -> FailableUnit {
+        outputs::launch(context, &context.binary_name)
+    }
+}
+
+pub fn build(build_target: &dyn BuildTarget, context: &Context) -> FailableUnit {
+    context.print_summary();
+
+    run_phase(context, "prepare-dependencies", &mut || build_target.prepare_dependencies(context))?;
+    run_phase(context, "process-assets", &mut || build_target.process_assets(context))?;
+    run_phase(context, "compile", &mut || build_target.compile(context))?;
+    run_phase(context, "create-output", &mut || create_output(build_target, context))
+}
+
+pub fn package(build_target: &dyn BuildTarget, context: &Context) -> FailableUnit {
+    let stage_dir = packages::stage(context)?;
+
+    run_phase(context, "prepare-package", &mut || build_target.prepare_package(context, &stage_dir))?;
+    packages::archive(context, &stage_dir)?;
+    run_phase(context, "package", &mut || build_target.package(context, &stage_dir))
+}
+
+pub fn xcode_only(context: &Context) -> FailableUnit {
+    Err(format!(
+        "Target '{}' can't be launched by crust-build, open its Xcode project in {:?} instead.",
+        context.target.id(),
+        &context.target_home_dir
+    )
+    .into())
+}
+
+pub fn create_output(build_target: &dyn BuildTarget, context: &Context) -> FailableUnit {
+    build_target.create_output(context)?;
+
+    let output_files = build_target.output_files(context)?;
+
+    if output_files.is_empty() {
+        return Ok(());
+    }
+
+    let mut fingerprint = Fingerprint::new().value(context.profile.id()).output(&outputs::output_dir(context));
+
+    for output_file in &output_files {
+        fingerprint = if output_file.is_dir() {
+            fingerprint.dir(output_file)?
+        } else {
+            fingerprint.file(output_file)?
+        };
+    }
+
+    let fingerprint = build_target.output_fingerprint(context, fingerprint)?;
+
+    fingerprints::run_step(context, &format!("collect-outputs-{}", context.profile.id()), &fingerprint, &mut || {
+        logs::out(log_tag!(), "Creating product ...");
+
+        outputs::clean(context)?;
+        outputs::collect(context, output_files.clone())?;
+        build_target.complete_output(context)
+    })
+}
+
+fn run_phase(context: &Context, phase: &str, work: &mut dyn FnMut() -> FailableUnit) -> FailableUnit {
+    logs::out(log_tag!(), &format!("Phase '{}' for target '{}' ...", phase, context.target.id()));
+
+    let started = Instant::now();
+    let result = work();
+    build_report::record_step(context, &format!("phase-{}", phase), false, started.elapsed());
+
+    result
+}
+
+// Holds what 'prepare_dependencies' resolved (a toolchain, where libraries were found) so the later phases of the same
+// build can use it rather than probing the host again.
+pub struct Resolved<T> {
+    value: RefCell<Option<T>>,
+}
+
+impl<T> Resolved<T> {
+    pub fn set(&self, value: T) {
+        self.value.replace(Some(value));
+    }
+
+    pub fn get(&self) -> Failable<Ref<'_, T>> {
+        let value = self.value.borrow();
+
+        if value.is_none() {
+            return Err("Dependencies must be prepared before they can be used".into());
+        }
+
+        Ok(Ref::map(value, |value| value.as_ref().unwrap()))
+    }
+}
+
+impl<T> Default for Resolved<T> {
+    fn default() -> Self {
+        Resolved {
+            value: RefCell::new(None),
+        }
+    }
+}
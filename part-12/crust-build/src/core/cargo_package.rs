@@ -0,0 +1,151 @@
+use crate::core::{failable::Failable, io, manifests::lookup};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct CargoTarget {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+// The parts of a crate's Cargo.toml file that crust-build needs in order to compile it and find what it produced.
+#[derive(Debug, Clone)]
+pub struct CargoPackage {
+    pub name: String,
+    pub source_dir: PathBuf,
+    pub library: Option<CargoTarget>,
+    pub binaries: Vec<CargoTarget>,
+}
+
+impl CargoPackage {
+    // The selection is either the directory of a crate under the project root, or the name of a package in one of them.
+    pub fn find(root_dir: &PathBuf, selection: &str) -> Failable<CargoPackage> {
+        let selected_dir = root_dir.join(selection);
+
+        if selected_dir.join("Cargo.toml").exists() {
+            return CargoPackage::load(&selected_dir);
+        }
+
+        let mut candidates: Vec<PathBuf> = std::fs::read_dir(root_dir)?.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+        candidates.sort();
+
+        for candidate in candidates.iter().filter(|candidate| candidate.join("Cargo.toml").exists()) {
+            if let Ok(package) = CargoPackage::load(candidate) {
+                if package.name == selection {
+                    return Ok(package);
+                }
+            }
+        }
+
+        Err(format!("No crate directory or package named '{}' found in {:?}", selection, root_dir).into())
+    }
+
+    #[allow(clippy::ptr_arg)]
+    pub fn load(source_dir: &PathBuf) -> Failable<CargoPackage> {
+        let manifest_path = source_dir.join("Cargo.toml");
+        let manifest = io::read_string(&manifest_path)?.parse::<toml_edit::Document>()?;
+
+        let name = lookup(manifest.as_item(), &["package", "name"])
+            .as_str()
+            .ok_or(format!("Field 'package/name' not found in {:?}", &manifest_path))?
+            .to_owned();
+
+        // Cargo's own defaults apply to anything the manifest leaves out: a library named after the package in 'src/lib.rs',
+        // a binary named after the package in 'src/main.rs' and a binary for every file in 'src/bin'.
+        let library_path = source_dir.join(lookup(manifest.as_item(), &["lib", "path"]).as_str().unwrap_or("src/lib.rs"));
+        let library = if manifest.contains_key("lib") || library_path.exists() {
+            Some(CargoTarget {
+                name: lookup(manifest.as_item(), &["lib", "name"])
+                    .as_str()
+                    .map(|name| name.to_owned())
+                    .unwrap_or_else(|| name.replace('-', "_")),
+                path: library_path,
+            })
+        } else {
+            None
+        };
+
+        let mut binaries = Vec::new();
+
+        if let Some(bins) = lookup(manifest.as_item(), &["bin"]).as_array_of_tables() {
+            for bin in bins.iter() {
+                let bin_name = bin
+                    .get("name")
+                    .and_then(|name| name.as_str())
+                    .ok_or(format!("A '[[bin]]' in {:?} has no name", &manifest_path))?;
+                let bin_path = match bin.get("path").and_then(|path| path.as_str()) {
+                    Some(path) => source_dir.join(path),
+                    None if bin_name == name => source_dir.join("src").join("main.rs"),
+                    None => source_dir.join("src").join("bin").join(format!("{}.rs", bin_name)),
+                };
+
+                binaries.push(CargoTarget {
+                    name: bin_name.to_owned(),
+                    path: bin_path,
+                });
+            }
+        }
+
+        if lookup(manifest.as_item(), &["package", "autobins"]).as_bool().unwrap_or(true) {
+            let mut discovered = vec![(name.clone(), source_dir.join("src").join("main.rs"))];
+
+            if let Ok(entries) = std::fs::read_dir(source_dir.join("src").join("bin")) {
+                let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+                paths.sort();
+
+                for path in paths.into_iter().filter(|path| path.extension().map_or(false, |extension| extension == "rs")) {
+                    if let Some(stem) = path.file_stem() {
+                        discovered.push((stem.to_string_lossy().to_string(), path.clone()));
+                    }
+                }
+            }
+
+            for (bin_name, bin_path) in discovered {
+                let is_declared = binaries.iter().any(|binary| binary.name == bin_name || binary.path == bin_path);
+
+                if bin_path.exists() && !is_declared {
+                    binaries.push(CargoTarget {
+                        name: bin_name,
+                        path: bin_path,
+                    });
+                }
+            }
+        }
+
+        Ok(CargoPackage {
+            name,
+            source_dir: source_dir.clone(),
+            library,
+            binaries,
+        })
+    }
+
+    pub fn library(&self) -> Failable<&CargoTarget> {
+        self.library
+            .as_ref()
+            .ok_or_else(|| format!("Package '{}' has no library target, which this target needs.", self.name).into())
+    }
+
+    pub fn binary(&self, name: &str) -> Failable<&CargoTarget> {
+        self.binaries.iter().find(|binary| binary.name == name).ok_or_else(|| {
+            format!("Package '{}' has no binary named '{}', it has: {}", self.name, name, self.binary_names().join(", ")).into()
+        })
+    }
+
+    // A package with a single binary needs no '--bin', one with only a library (for the mobile targets) is named after the package.
+    pub fn default_binary_name(&self) -> Failable<String> {
+        match self.binaries.as_slice() {
+            [] => Ok(self.name.clone()),
+            [binary] => Ok(binary.name.clone()),
+            _ => Err(format!(
+                "Package '{}' has several binaries, choose one with '--bin': {}",
+                self.name,
+                self.binary_names().join(", ")
+            )
+            .into()),
+        }
+    }
+
+    fn binary_names(&self) -> Vec<String> {
+        self.binaries.iter().map(|binary| binary.name.clone()).collect()
+    }
+}
@@ -0,0 +1,256 @@
+use crate::core::{failable::Failable, io};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::PathBuf};
+
+pub const CONFIG_FILE_NAME: &str = "crust-build.toml";
+
+const SDL2_SOURCE_URL: &str = "https://www.libsdl.org/release/SDL2-2.0.14.zip";
+const SDL2_IMAGE_SOURCE_URL: &str = "https://www.libsdl.org/projects/SDL_image/release/SDL2_image-2.0.5.zip";
+
+const ANDROID_ARCHITECTURES: [&str; 4] = ["arm64-v8a", "armeabi-v7a", "x86", "x86_64"];
+
+// Every section and field is optional - anything not specified in the project config file falls back to these defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub project: ProjectConfig,
+    pub downloads: DownloadsConfig,
+    pub assets: AssetsConfig,
+    pub android: AndroidConfig,
+    pub emscripten: EmscriptenConfig,
+    pub ios: IosConfig,
+    pub linux: LinuxConfig,
+    pub macos: MacOSConfig,
+    pub windows: WindowsConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub package: String,
+    pub binary_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DownloadsConfig {
+    pub mirror_url: Option<String>,
+    pub checksums: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AssetsConfig {
+    pub texture_format: TextureFormat,
+    pub validate_shaders: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextureFormat {
+    // 8 bits per channel, the same pixels the application previously got from decoding the PNG itself.
+    Rgba8,
+    // 16 bits per pixel without alpha, half the memory for opaque textures.
+    Rgb565,
+    // 16 bits per pixel with 4 bits of alpha.
+    Rgba4444,
+}
+
+impl TextureFormat {
+    pub fn id(&self) -> &str {
+        match self {
+            TextureFormat::Rgba8 => "rgba8",
+            TextureFormat::Rgb565 => "rgb565",
+            TextureFormat::Rgba4444 => "rgba4444",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AndroidConfig {
+    pub sdl2_source_url: String,
+    pub sdl2_image_source_url: String,
+    pub app_platform: String,
+    pub ndk_api_level: Option<u32>,
+    pub architectures: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmscriptenConfig {
+    pub version: String,
+    pub sdk_url: String,
+    pub flags: String,
+    pub bind_address: String,
+    pub port: u16,
+    pub open_browser: bool,
+    pub watch: bool,
+    pub html_template: Option<String>,
+    pub title: String,
+    pub canvas_width: u32,
+    pub canvas_height: u32,
+    pub canvas_mode: CanvasMode,
+    pub show_console: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CanvasMode {
+    // The canvas keeps the configured width and height.
+    Fixed,
+    // The canvas fills the browser window and follows it when resized.
+    Responsive,
+    // Like responsive, but clicking the canvas also switches the browser into fullscreen.
+    Fullscreen,
+}
+
+impl CanvasMode {
+    pub fn id(&self) -> &str {
+        match self {
+            CanvasMode::Fixed => "fixed",
+            CanvasMode::Responsive => "responsive",
+            CanvasMode::Fullscreen => "fullscreen",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IosConfig {
+    pub sdl2_url: String,
+    pub sdl2_image_url: String,
+    pub device_architectures: Vec<String>,
+    pub simulator_architectures: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinuxConfig {
+    pub sdl2_url: String,
+    pub sdl2_image_url: String,
+    pub description: String,
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MacOSConfig {
+    pub sdl2_url: String,
+    pub sdl2_image_url: String,
+    pub desktop_architectures: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowsConfig {
+    pub sdl2_url: String,
+    pub sdl2_image_url: String,
+    pub mingw_sdl2_url: String,
+    pub mingw_sdl2_image_url: String,
+}
+
+impl Config {
+    pub fn load(config_file: &PathBuf) -> Failable<Config> {
+        if !config_file.exists() {
+            return Ok(Config::default());
+        }
+
+        toml::from_str(&io::read_string(config_file)?).map_err(|error| format!("Invalid {:?}: {}", config_file, error).into())
+    }
+}
+
+impl Default for ProjectConfig {
+    fn default() -> Self {
+        ProjectConfig {
+            package: "crust-main".to_owned(),
+            binary_name: None,
+        }
+    }
+}
+
+impl Default for AssetsConfig {
+    fn default() -> Self {
+        AssetsConfig {
+            texture_format: TextureFormat::Rgba8,
+            validate_shaders: false,
+        }
+    }
+}
+
+impl Default for AndroidConfig {
+    fn default() -> Self {
+        AndroidConfig {
+            sdl2_source_url: SDL2_SOURCE_URL.to_owned(),
+            sdl2_image_source_url: SDL2_IMAGE_SOURCE_URL.to_owned(),
+            app_platform: "android-21".to_owned(),
+            ndk_api_level: None,
+            architectures: ANDROID_ARCHITECTURES.iter().map(|architecture| architecture.to_string()).collect(),
+        }
+    }
+}
+
+impl Default for EmscriptenConfig {
+    fn default() -> Self {
+        EmscriptenConfig {
+            version: "2.0.32".to_owned(),
+            sdk_url: "https://github.com/emscripten-core/emsdk/archive/refs/tags/2.0.32.zip".to_owned(),
+            flags: r#"-s EXPORTED_FUNCTIONS='["_main","_fileno"]' -O2 -s USE_SDL=2 -s USE_SDL_IMAGE=2 -s SDL2_IMAGE_FORMATS='["png"]' -s USE_WEBGL2=1"#
+                .to_owned(),
+            bind_address: "127.0.0.1".to_owned(),
+            port: 8000,
+            open_browser: true,
+            watch: false,
+            html_template: None,
+            title: "crust".to_owned(),
+            canvas_width: 600,
+            canvas_height: 360,
+            canvas_mode: CanvasMode::Fixed,
+            show_console: true,
+        }
+    }
+}
+
+impl Default for IosConfig {
+    fn default() -> Self {
+        IosConfig {
+            sdl2_url: SDL2_SOURCE_URL.to_owned(),
+            sdl2_image_url: SDL2_IMAGE_SOURCE_URL.to_owned(),
+            device_architectures: vec!["aarch64-apple-ios".to_owned()],
+            simulator_architectures: vec!["aarch64-apple-ios-sim".to_owned(), "x86_64-apple-ios".to_owned()],
+        }
+    }
+}
+
+impl Default for LinuxConfig {
+    fn default() -> Self {
+        LinuxConfig {
+            sdl2_url: SDL2_SOURCE_URL.to_owned(),
+            sdl2_image_url: SDL2_IMAGE_SOURCE_URL.to_owned(),
+            description: "A crust application.".to_owned(),
+            icon: None,
+        }
+    }
+}
+
+impl Default for MacOSConfig {
+    fn default() -> Self {
+        MacOSConfig {
+            sdl2_url: SDL2_SOURCE_URL.to_owned(),
+            sdl2_image_url: SDL2_IMAGE_SOURCE_URL.to_owned(),
+            desktop_architectures: vec!["x86_64-apple-darwin".to_owned(), "aarch64-apple-darwin".to_owned()],
+        }
+    }
+}
+
+impl Default for WindowsConfig {
+    fn default() -> Self {
+        WindowsConfig {
+            sdl2_url: "https://www.libsdl.org/release/SDL2-devel-2.0.14-VC.zip".to_owned(),
+            sdl2_image_url: "https://www.libsdl.org/projects/SDL_image/release/SDL2_image-devel-2.0.5-VC.zip".to_owned(),
+            mingw_sdl2_url: "https://www.libsdl.org/release/SDL2-devel-2.0.14-mingw.tar.gz".to_owned(),
+            mingw_sdl2_image_url: "https://www.libsdl.org/projects/SDL_image/release/SDL2_image-devel-2.0.5-mingw.tar.gz"
+                .to_owned(),
+        }
+    }
+}
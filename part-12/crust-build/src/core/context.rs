@@ -1,44 +1,260 @@
 use crate::{
-    core::{logs, target::Target, variant::Variant},
+    core::{
+        build_report::BuildReport,
+        cargo_package::CargoPackage,
+        config::{Config, CONFIG_FILE_NAME},
+        executor::{Executor, SystemExecutor},
+        failable::Failable,
+        failable_unit::FailableUnit,
+        logs,
+        profile::Profile,
+        target::Target,
+        variant::Variant,
+    },
     log_tag,
 };
-use std::path::PathBuf;
+use std::{path::PathBuf, rc::Rc};
+
+// Everything chosen on the command line, crust-build.toml decides whatever isn't chosen here. The executor is part of it
+// so even reading the configuration happens through the one a dry run uses.
+pub struct Options<'a> {
+    pub target: Target,
+    pub variant: Variant,
+    pub profile: Option<&'a str>,
+    pub package: Option<&'a str>,
+    pub binary: Option<&'a str>,
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+    pub offline: bool,
+    pub update_lock: bool,
+    pub force: bool,
+    pub assemble: bool,
+    pub android_abis: Option<Vec<String>>,
+    pub emscripten_port: Option<u16>,
+    pub emscripten_bind_address: Option<&'a str>,
+    pub emscripten_no_open: bool,
+    pub emscripten_watch: bool,
+    pub executor: Rc<dyn Executor>,
+}
+
+impl<'a> Options<'a> {
+    pub fn new(target: Target, variant: Variant) -> Self {
+        Self {
+            target,
+            variant,
+            profile: None,
+            package: None,
+            binary: None,
+            features: Vec::new(),
+            no_default_features: false,
+            offline: false,
+            update_lock: false,
+            force: false,
+            assemble: false,
+            android_abis: None,
+            emscripten_port: None,
+            emscripten_bind_address: None,
+            emscripten_no_open: false,
+            emscripten_watch: false,
+            executor: Rc::new(SystemExecutor),
+        }
+    }
+}
 
 pub struct Context {
+    pub assemble: bool,
     pub assets_dir: PathBuf,
+    pub binary_name: String,
+    pub build_report: BuildReport,
+    pub config: Config,
+    pub config_file: PathBuf,
+    pub downloads_cache_dir: PathBuf,
+    pub downloads_lock_file: PathBuf,
+    pub executor: Rc<dyn Executor>,
+    pub features: Vec<String>,
+    pub force: bool,
+    pub no_default_features: bool,
+    pub offline: bool,
+    pub package: CargoPackage,
+    pub profile: Profile,
+    pub root_dir: PathBuf,
     pub rust_build_dir: PathBuf,
     pub source_dir: PathBuf,
+    pub target: Target,
     pub target_home_dir: PathBuf,
+    pub update_lock: bool,
     pub variant: Variant,
     pub working_dir: PathBuf,
 }
 
 impl Context {
-    pub fn new(root_dir: PathBuf, target: Target, variant: Variant) -> Self {
+    pub fn new(root_dir: PathBuf, options: Options) -> Failable<Self> {
+        let target = options.target;
         let target_home_dir = root_dir.join(target.id());
         let working_dir = target_home_dir.join(".rust-build");
         let rust_build_dir = working_dir.join("rust");
-        let source_dir = root_dir.join("crust-main");
+        let config_file = root_dir.join(CONFIG_FILE_NAME);
+        let mut config = Config::load(&config_file)?;
+        let package = CargoPackage::find(&root_dir, options.package.unwrap_or(&config.project.package))?;
+        let source_dir = package.source_dir.clone();
         let assets_dir = source_dir.join("assets");
 
-        Context {
-            assets_dir: assets_dir,
-            rust_build_dir: rust_build_dir,
-            source_dir: source_dir,
-            target_home_dir: target_home_dir,
-            variant: variant,
-            working_dir: working_dir,
+        // A profile decides the variant too, based on whether it ultimately inherits from Cargo's 'dev' or 'release' profile.
+        let profile = match options.profile {
+            Some(profile) => Profile::resolve(profile, &source_dir.join("Cargo.toml"))?,
+            None => Profile::of_variant(options.variant),
+        };
+
+        // The configured binary only applies to the configured crate. Which binary is only needed once building, so a crate
+        // with several of them leaves it empty for 'require_binary' to report rather than failing commands like 'clean'.
+        let binary_name = match (options.binary, options.package, config.project.binary_name.as_deref()) {
+            (Some(binary_name), _, _) | (None, None, Some(binary_name)) => package.binary(binary_name)?.name.clone(),
+            _ => package.default_binary_name().unwrap_or_default(),
+        };
+        let downloads_lock_file = root_dir.join("crust-build").join("downloads.lock");
+
+        // Downloaded archives are shared by all targets. The cache location can be moved (for example onto a CI cache volume) via an environment variable.
+        let downloads_cache_dir = match std::env::var("CRUST_BUILD_CACHE_DIR") {
+            Ok(cache_dir) => PathBuf::from(cache_dir),
+            _ => root_dir.join(".rust-build").join("downloads"),
+        };
+
+        // An ABI filter on the command line replaces the configured architectures so it reaches both 'ndk-build' and the Rust compile.
+        if let Some(abis) = options.android_abis {
+            config.android.architectures = abis;
         }
+
+        if let Some(port) = options.emscripten_port {
+            config.emscripten.port = port;
+        }
+
+        if let Some(bind_address) = options.emscripten_bind_address {
+            config.emscripten.bind_address = bind_address.to_owned();
+        }
+
+        config.emscripten.open_browser &= !options.emscripten_no_open;
+        config.emscripten.watch |= options.emscripten_watch;
+
+        Ok(Context {
+            assemble: options.assemble,
+            assets_dir,
+            binary_name,
+            build_report: BuildReport::default(),
+            config,
+            config_file,
+            downloads_cache_dir,
+            downloads_lock_file,
+            executor: options.executor,
+            features: options.features,
+            force: options.force,
+            no_default_features: options.no_default_features,
+            offline: options.offline,
+            package,
+            variant: profile.variant,
+            profile,
+            root_dir,
+            rust_build_dir,
+            source_dir,
+            target,
+            target_home_dir,
+            update_lock: options.update_lock,
+            working_dir,
+        })
     }
 
     pub fn print_summary(&self) {
         logs::out(log_tag!(), "---------------------------------------------");
+        logs::out(log_tag!(), &format!("Target:              {}", self.target.id()));
         logs::out(log_tag!(), &format!("Assets dir:          {:?}", self.assets_dir));
         logs::out(log_tag!(), &format!("Working dir:         {:?}", self.working_dir));
         logs::out(log_tag!(), &format!("Rust build dir:      {:?}", self.rust_build_dir));
         logs::out(log_tag!(), &format!("Variant:             {:?}", self.variant));
+        logs::out(log_tag!(), &format!("Profile:             {}", self.profile.name));
+        logs::out(log_tag!(), &format!("Features:            {:?}", self.features));
+        logs::out(log_tag!(), &format!("No default features: {:?}", self.no_default_features));
         logs::out(log_tag!(), &format!("Target home dir:     {:?}", self.target_home_dir));
         logs::out(log_tag!(), &format!("Main source dir:     {:?}", self.source_dir));
+        logs::out(log_tag!(), &format!("Package:             {}", self.package.name));
+        logs::out(log_tag!(), &format!("Binary:              {}", self.binary_name));
+        logs::out(log_tag!(), &format!("Config file:         {:?} (exists: {})", self.config_file, self.config_file.exists()));
+        logs::out(log_tag!(), &format!("Downloads cache dir: {:?}", self.downloads_cache_dir));
+        logs::out(log_tag!(), &format!("Offline:             {:?}", self.offline));
+        logs::out(log_tag!(), &format!("Update lock:         {:?}", self.update_lock));
+        logs::out(log_tag!(), &format!("Force:               {:?}", self.force));
+        logs::out(log_tag!(), &format!("Assemble:            {:?}", self.assemble));
         logs::out(log_tag!(), "---------------------------------------------");
     }
+
+    pub fn require_binary(&self) -> FailableUnit {
+        if self.binary_name.is_empty() {
+            self.package.default_binary_name()?;
+        }
+
+        Ok(())
+    }
+
+    // The profile and feature arguments shared by every 'cargo rustc' invocation of the platform modules.
+    pub fn cargo_build_flags(&self) -> String {
+        let mut flags = vec![self.profile.rust_compiler_flag()];
+
+        if !self.features.is_empty() {
+            flags.push(format!("--features {:?}", self.features.join(",")));
+        }
+
+        if self.no_default_features {
+            flags.push("--no-default-features".to_owned());
+        }
+
+        flags.retain(|flag| !flag.is_empty());
+        flags.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_crate(root_dir: &std::path::Path, name: &str, binaries: &[&str]) {
+        let source_dir = root_dir.join(name);
+        std::fs::create_dir_all(source_dir.join("src").join("bin")).unwrap();
+        std::fs::write(source_dir.join("Cargo.toml"), format!("[package]\nname = {:?}\nversion = \"1.0.0\"\n", name)).unwrap();
+        std::fs::write(source_dir.join("src").join("main.rs"), "fn main() {}").unwrap();
+
+        for binary in binaries {
+            std::fs::write(source_dir.join("src").join("bin").join(format!("{}.rs", binary)), "fn main() {}").unwrap();
+        }
+    }
+
+    #[test]
+    fn selects_the_binary_once_the_command_line_is_known() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root_dir = temp_dir.path().to_path_buf();
+        write_crate(&root_dir, "crust-main", &["tool"]);
+        write_crate(&root_dir, "other", &[]);
+
+        let create = |package: Option<&str>, binary: Option<&str>| {
+            Context::new(
+                root_dir.clone(),
+                Options {
+                    package,
+                    binary,
+                    ..Options::new(Target::Linux, Variant::Debug)
+                },
+            )
+        };
+
+        // Commands which never build, like 'clean', don't need to know which of several binaries is meant.
+        let context = create(None, None).unwrap();
+        assert!(context.require_binary().unwrap_err().to_string().contains("choose one with '--bin': crust-main, tool"));
+
+        let context = create(None, Some("tool")).unwrap();
+        assert_eq!(context.binary_name, "tool");
+        context.require_binary().unwrap();
+
+        let context = create(Some("other"), None).unwrap();
+        assert_eq!(context.package.name, "other");
+        assert_eq!(context.binary_name, "other");
+
+        assert!(create(None, Some("missing")).is_err());
+    }
 }
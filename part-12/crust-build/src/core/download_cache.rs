@@ -0,0 +1,108 @@
+use crate::{
+    core::{context::Context, downloads, failable::Failable, failable_unit::FailableUnit, hashes, io, logs},
+    log_tag,
+};
+use std::path::PathBuf;
+
+const LOCK_FILE_HEADER: &str = "# Pinned SHA-256 checksums of third party archives downloaded by crust-build, keyed by url.\n# Commit this file so every machine verifies the exact same archives.\n";
+
+pub fn resolve(context: &Context, url: &str) -> Failable<PathBuf> {
+    let pinned_checksum = pinned_checksum(context, url)?;
+
+    // An archive nobody has vouched for is never trusted implicitly, pinning it has to be asked for with '--update-lock'.
+    if pinned_checksum.is_none() && !context.update_lock {
+        return Err(format!(
+            "Archive {:?} has no pinned checksum in {:?} or in '[downloads.checksums]' of {:?}. Pin it after checking where it comes from by running again with '--update-lock'.",
+            url, &context.downloads_lock_file, &context.config_file
+        )
+        .into());
+    }
+
+    // If we already know the checksum for this url and the cache holds an archive with that content, we don't need the network at all.
+    if let Some(checksum) = &pinned_checksum {
+        let cached_path = cached_path(context, checksum);
+
+        if cached_path.exists() {
+            if &hashes::sha256_file(&cached_path)? == checksum {
+                logs::out(log_tag!(), &format!("Using cached download for: {:?}", url));
+                return Ok(cached_path);
+            }
+
+            logs::out(log_tag!(), &format!("Cached download is corrupt, discarding: {:?}", &cached_path));
+            io::delete(context, &cached_path)?;
+        }
+    }
+
+    if context.offline {
+        return Err(match &pinned_checksum {
+            Some(checksum) => format!(
+                "Offline mode: archive {:?} (sha256 {}) is not in the download cache {:?}. Run once without '--offline' or copy the archive into the cache.",
+                url, checksum, &context.downloads_cache_dir
+            ),
+            None => format!("Offline mode: archive {:?} can't be downloaded to pin its checksum. Run once without '--offline'.", url),
+        }
+        .into());
+    }
+
+    // Download into the cache directory first so the final move into place is a cheap rename on the same file system. The partial
+    // file is named after the url and left behind if the download is interrupted, so the next attempt can resume from where it got to.
+    let partial_path = context.downloads_cache_dir.join("partial").join(hashes::sha256_bytes(url.as_bytes()));
+    downloads::download(context, url, &partial_path)?;
+
+    let checksum = hashes::sha256_file(&partial_path)?;
+
+    match &pinned_checksum {
+        Some(expected) if expected != &checksum => {
+            io::delete(context, &partial_path)?;
+            let message = format!("Checksum mismatch for {:?}: expected sha256 {} but downloaded {}", url, expected, checksum);
+            return Err(message.into());
+        }
+        Some(_) => {}
+        None => {
+            logs::out(log_tag!(), &format!("Pinning new checksum for {:?}: {}", url, &checksum));
+            write_pinned_checksum(context, &context.downloads_lock_file, url, &checksum)?;
+        }
+    }
+
+    let cached_path = cached_path(context, &checksum);
+    io::create_dir(context, &context.downloads_cache_dir.join("sha256"))?;
+    io::rename(context, &partial_path, &cached_path)?;
+
+    Ok(cached_path)
+}
+
+fn cached_path(context: &Context, checksum: &str) -> PathBuf {
+    context.downloads_cache_dir.join("sha256").join(checksum)
+}
+
+fn read_lock_file(lock_file: &PathBuf) -> Failable<toml_edit::Document> {
+    if lock_file.exists() {
+        Ok(io::read_string(lock_file)?.parse::<toml_edit::Document>()?)
+    } else {
+        Ok(toml_edit::Document::new())
+    }
+}
+
+// A checksum configured next to an overridden url in crust-build.toml wins over the lock file.
+fn pinned_checksum(context: &Context, url: &str) -> Failable<Option<String>> {
+    match context.config.downloads.checksums.get(url) {
+        Some(checksum) => Ok(Some(checksum.to_lowercase())),
+        None => read_pinned_checksum(&context.downloads_lock_file, url),
+    }
+}
+
+fn read_pinned_checksum(lock_file: &PathBuf, url: &str) -> Failable<Option<String>> {
+    Ok(read_lock_file(lock_file)?.get(url).and_then(|checksum| checksum.as_str()).map(|checksum| checksum.to_owned()))
+}
+
+fn write_pinned_checksum(context: &Context, lock_file: &PathBuf, url: &str, checksum: &str) -> FailableUnit {
+    let mut lock = read_lock_file(lock_file)?;
+    lock[url] = toml_edit::value(checksum);
+
+    // A brand new lock file gets a short explanation at the top, existing files keep whatever comments they already have.
+    if lock_file.exists() {
+        io::write_string(context, &lock.to_string(), lock_file)
+    } else {
+        io::write_string(context, &format!("{}\n{}", LOCK_FILE_HEADER, lock), lock_file)
+    }
+}
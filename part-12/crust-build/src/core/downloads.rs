@@ -1,23 +1,313 @@
 use crate::{
-    core::{failable_unit::FailableUnit, io, logs},
+    core::{context::Context, failable_unit::FailableUnit, io, logs},
     log_tag,
 };
-use std::path::Path;
+use reqwest::{blocking::Response, header, StatusCode};
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Progress is logged every 10 percent, or every 10 MB if the server doesn't tell us how large the download is.
+const PROGRESS_STEP_PERCENT: u64 = 10;
+const PROGRESS_STEP_BYTES: u64 = 10 * 1024 * 1024;
+
+pub fn download(context: &Context, url: &str, destination: &Path) -> FailableUnit {
+    if let Some(mirror_url) = mirror_url(context, url) {
+        match fetch(context, &mirror_url, destination) {
+            Ok(_) => return Ok(()),
+            Err(error) => logs::out(log_tag!(), &format!("Mirror download failed, falling back to {:?}: {}", url, error)),
+        }
+
+        // Whatever the mirror sent is no use for resuming from the origin, which may well serve different bytes.
+        discard_partial(context, destination)?;
+    }
+
+    fetch(context, url, destination)
+}
+
+fn mirror_url(context: &Context, url: &str) -> Option<String> {
+    let mirror_url = context.config.downloads.mirror_url.as_ref()?;
+    let host_and_path = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+
+    Some(format!("{}/{}", mirror_url.trim_end_matches('/'), host_and_path))
+}
 
-pub fn download(url: &str, destination: &Path) -> FailableUnit {
+fn fetch(context: &Context, url: &str, destination: &Path) -> FailableUnit {
     logs::out(log_tag!(), &format!("Download: {:?}", url));
     logs::out(log_tag!(), &format!("Into: {:?}", destination));
 
-    let response = reqwest::blocking::get(url)?;
+    // The client picks up HTTP_PROXY, HTTPS_PROXY and NO_PROXY from the environment by itself. There is deliberately no overall
+    // timeout, as the default one would cut off large archives on a slow connection.
+    let client = reqwest::blocking::Client::builder().connect_timeout(CONNECT_TIMEOUT).timeout(None).build()?;
+
+    let existing_size = std::fs::metadata(destination).map(|metadata| metadata.len()).unwrap_or(0);
+    let validator = std::fs::read_to_string(validator_path(destination)).ok();
+    let mut request = client.get(url);
+
+    // A partial file can only be resumed if the server can tell us whether it still has the content it came from. With
+    // 'If-Range' a server whose content changed sends all of the new content instead of a range of it.
+    match &validator {
+        Some(validator) if existing_size > 0 => {
+            request = request.header(header::RANGE, format!("bytes={}-", existing_size)).header(header::IF_RANGE, validator);
+        }
+        _ if existing_size > 0 => discard_partial(context, destination)?,
+        _ => {}
+    }
+
+    let mut response = request.send()?;
+
+    let (mut file, offset) = match response.status() {
+        StatusCode::PARTIAL_CONTENT if resumes_at(&response, existing_size) => {
+            logs::out(log_tag!(), &format!("Resuming partial download from {} bytes", existing_size));
+            (OpenOptions::new().append(true).open(destination)?, existing_size)
+        }
+        // The partial file is already as long as (or longer than) the content, or the range sent isn't the one we asked for,
+        // so what we have can't be trusted and we start again.
+        StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE if existing_size > 0 => {
+            discard_partial(context, destination)?;
+            return fetch(context, url, destination);
+        }
+        // A server which ignores the range request, or whose content changed, sends all of it, so anything we had is replaced.
+        status if status.is_success() => {
+            io::create_dir(context, &destination.parent().ok_or("Parent directory not found")?.to_path_buf())?;
+            write_validator(context, &response, destination)?;
+            (File::create(destination)?, 0)
+        }
+        status => return Err(format!("Url request was not successful: {:?} ({})", url, status).into()),
+    };
+
+    let total_size = response.content_length().map(|length| length + offset);
+    let mut downloaded = offset;
+    let mut next_report = next_progress_report(downloaded, total_size);
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let count = response.read(&mut buffer)?;
+
+        if count == 0 {
+            break;
+        }
+
+        file.write_all(&buffer[..count])?;
+        downloaded += count as u64;
 
-    if !response.status().is_success() {
-        return Err("Url request was not successful.".into());
+        if downloaded >= next_report {
+            logs::out(log_tag!(), &progress(downloaded, total_size));
+            next_report = next_progress_report(downloaded, total_size);
+        }
     }
 
-    let content = response.bytes()?;
+    file.flush()?;
+
+    if let Some(total_size) = total_size {
+        if downloaded != total_size {
+            return Err(format!("Download of {:?} ended early after {} of {} bytes.", url, downloaded, total_size).into());
+        }
+    }
 
-    io::write_bytes(&content, &destination.to_path_buf())?;
     logs::out(log_tag!(), &format!("Download complete: {:?}", destination));
 
-    Ok(())
+    io::delete(context, &validator_path(destination))
+}
+
+fn validator_path(destination: &Path) -> PathBuf {
+    let mut file_name = destination.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".validator");
+    destination.with_file_name(file_name)
+}
+
+// A weak ETag can't be used with 'If-Range', and without any validator a later resume could splice together two versions.
+fn write_validator(context: &Context, response: &Response, destination: &Path) -> FailableUnit {
+    let header = |name: header::HeaderName| response.headers().get(name).and_then(|value| value.to_str().ok());
+    let validator = match header(header::ETAG) {
+        Some(etag) if !etag.starts_with("W/") => Some(etag),
+        _ => header(header::LAST_MODIFIED),
+    };
+
+    match validator {
+        Some(validator) => io::write_string(context, validator, &validator_path(destination)),
+        None => io::delete(context, &validator_path(destination)),
+    }
+}
+
+fn resumes_at(response: &Response, offset: u64) -> bool {
+    // Content-Range: bytes 1024-2047/2048
+    let content_range = response.headers().get(header::CONTENT_RANGE).and_then(|value| value.to_str().ok()).unwrap_or_default();
+
+    content_range.strip_prefix("bytes ").and_then(|range| range.split('-').next()) == Some(&offset.to_string())
+}
+
+fn discard_partial(context: &Context, destination: &Path) -> FailableUnit {
+    io::delete(context, &destination.to_path_buf())?;
+    io::delete(context, &validator_path(destination))
+}
+
+fn next_progress_report(downloaded: u64, total_size: Option<u64>) -> u64 {
+    match total_size {
+        Some(total_size) if total_size > 0 => {
+            let step = (total_size * PROGRESS_STEP_PERCENT / 100).max(1);
+            (downloaded / step + 1) * step
+        }
+        _ => (downloaded / PROGRESS_STEP_BYTES + 1) * PROGRESS_STEP_BYTES,
+    }
+}
+
+fn progress(downloaded: u64, total_size: Option<u64>) -> String {
+    let megabytes = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+
+    match total_size {
+        Some(total_size) if total_size > 0 => format!(
+            "Downloaded {:.1} of {:.1} MB ({}%)",
+            megabytes(downloaded),
+            megabytes(total_size),
+            downloaded * 100 / total_size
+        ),
+        _ => format!("Downloaded {:.1} MB", megabytes(downloaded)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{context::Options, target::Target, variant::Variant};
+    use std::{net::TcpListener, thread::JoinHandle};
+
+    // Answers each connection with the next canned response and hands back the requests it received, lower cased.
+    fn serve(responses: Vec<Vec<u8>>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = std::thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|response| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut request = Vec::new();
+                    let mut byte = [0u8; 1];
+
+                    while !request.ends_with(b"\r\n\r\n") {
+                        stream.read_exact(&mut byte).unwrap();
+                        request.push(byte[0]);
+                    }
+
+                    stream.write_all(&response).unwrap();
+                    String::from_utf8_lossy(&request).to_lowercase()
+                })
+                .collect()
+        });
+
+        (url, server)
+    }
+
+    // The body may be shorter than 'content_length' to simulate a connection dropping halfway through.
+    fn response(status: &str, headers: &[&str], content_length: usize, body: &str) -> Vec<u8> {
+        let mut response = format!("HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\n", status, content_length);
+
+        for header in headers {
+            response += &format!("{}\r\n", header);
+        }
+
+        format!("{}\r\n{}", response, body).into_bytes()
+    }
+
+    fn context() -> Context {
+        let root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf();
+        Context::new(root_dir, Options::new(Target::Linux, Variant::Debug)).unwrap()
+    }
+
+    fn partial(temp_dir: &Path, content: &str, validator: Option<&str>) -> PathBuf {
+        let destination = temp_dir.join("partial").join("archive");
+        std::fs::create_dir_all(destination.parent().unwrap()).unwrap();
+        std::fs::write(&destination, content).unwrap();
+
+        if let Some(validator) = validator {
+            std::fs::write(validator_path(&destination), validator).unwrap();
+        }
+
+        destination
+    }
+
+    #[test]
+    fn resumes_a_partial_download_that_is_still_current() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = partial(temp_dir.path(), "hello ", Some(r#""v1""#));
+        let (url, server) = serve(vec![response("206 Partial Content", &["Content-Range: bytes 6-10/11"], 5, "world")]);
+
+        download(&context(), &format!("{}/archive.zip", url), &destination).unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("range: bytes=6-\r\n"));
+        assert!(requests[0].contains("if-range: \"v1\"\r\n"));
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "hello world");
+        assert!(!validator_path(&destination).exists());
+    }
+
+    #[test]
+    fn starts_again_when_the_range_is_not_satisfiable() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = partial(temp_dir.path(), "hello world, and more", Some(r#""v1""#));
+        let (url, server) = serve(vec![
+            response("416 Range Not Satisfiable", &["Content-Range: bytes */11"], 0, ""),
+            response("200 OK", &["ETag: \"v1\""], 11, "hello world"),
+        ]);
+
+        download(&context(), &format!("{}/archive.zip", url), &destination).unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("range: bytes=21-\r\n"));
+        assert!(!requests[1].contains("range:"));
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "hello world");
+    }
+
+    #[test]
+    fn replaces_the_partial_download_when_the_server_ignores_ranges() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = partial(temp_dir.path(), "stale ", Some(r#""v1""#));
+        let (url, server) = serve(vec![response("200 OK", &["ETag: \"v2\""], 11, "hello world")]);
+
+        download(&context(), &format!("{}/archive.zip", url), &destination).unwrap();
+
+        assert!(server.join().unwrap()[0].contains("range: bytes=6-\r\n"));
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "hello world");
+    }
+
+    #[test]
+    fn does_not_resume_without_a_validator() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = partial(temp_dir.path(), "stale ", None);
+        let (url, server) = serve(vec![response("200 OK", &[], 11, "hello world")]);
+
+        download(&context(), &format!("{}/archive.zip", url), &destination).unwrap();
+
+        assert!(!server.join().unwrap()[0].contains("range:"));
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "hello world");
+    }
+
+    #[test]
+    fn falls_back_to_the_origin_without_the_mirror_data() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().join("partial").join("archive");
+
+        // The mirror drops the connection halfway, leaving a partial file that it could have resumed.
+        let (url, server) = serve(vec![
+            response("200 OK", &["ETag: \"mirror\""], 11, "hello"),
+            response("200 OK", &["ETag: \"origin\""], 11, "hello world"),
+        ]);
+        let mut context = context();
+        context.config.downloads.mirror_url = Some(format!("{}/mirror", url));
+
+        download(&context, &format!("{}/archive.zip", url), &destination).unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("get /mirror/127.0.0.1:"));
+        assert!(requests[1].starts_with("get /archive.zip "));
+        assert!(!requests[1].contains("range:"));
+        assert!(!requests[1].contains("if-range:"));
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "hello world");
+    }
 }
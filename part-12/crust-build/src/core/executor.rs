@@ -0,0 +1,69 @@
+use crate::core::{
+    context::Context, failable::Failable, failable_unit::FailableUnit, script::Script, scripts, scripts::ScriptOutput,
+};
+use std::{path::PathBuf, process::Command};
+
+// Everything a build does to the outside world goes through the executor held by the context: running scripts, changing the
+// file system, downloading and asking the host about itself. The system executor does the real work, the recording executor
+// in 'build_plan.rs' only writes down what would have happened, which is what '--dry-run' and the tests use.
+pub trait Executor {
+    // Output is only captured when the caller needs it, otherwise the script writes straight to our own terminal.
+    fn run_script(&self, script: &Script, capture: bool) -> Failable<ScriptOutput>;
+
+    // The work performs the operation for real, an executor that doesn't run it only records the operation.
+    #[allow(clippy::ptr_arg)]
+    fn file_operation(
+        &self,
+        operation: &str,
+        source: Option<&PathBuf>,
+        destination: &PathBuf,
+        work: &mut dyn FnMut() -> FailableUnit,
+    ) -> FailableUnit;
+
+    #[allow(clippy::ptr_arg)]
+    fn download(&self, url: &str, destination: &PathBuf, work: &mut dyn FnMut() -> FailableUnit) -> FailableUnit;
+
+    // Probes are questions about the host, such as whether pkg-config can find SDL2 or which NDK is installed. The query asks
+    // the host itself, an answer of 'None' means whatever was asked about isn't available.
+    fn probe(&self, name: &str, query: &mut dyn FnMut() -> Option<String>) -> Option<String>;
+
+    // Steps such as the web server or the build report only make sense when the build actually ran.
+    fn is_recording(&self) -> bool {
+        false
+    }
+}
+
+pub struct SystemExecutor;
+
+impl Executor for SystemExecutor {
+    fn run_script(&self, script: &Script, capture: bool) -> Failable<ScriptOutput> {
+        scripts::execute_with_retries(script, capture)
+    }
+
+    fn file_operation(&self, _: &str, _: Option<&PathBuf>, _: &PathBuf, work: &mut dyn FnMut() -> FailableUnit) -> FailableUnit {
+        work()
+    }
+
+    fn download(&self, _: &str, _: &PathBuf, work: &mut dyn FnMut() -> FailableUnit) -> FailableUnit {
+        work()
+    }
+
+    fn probe(&self, _: &str, query: &mut dyn FnMut() -> Option<String>) -> Option<String> {
+        query()
+    }
+}
+
+// Runs a program only to learn something about the host, the answer is its trimmed output if it succeeded.
+pub fn probe_command(context: &Context, program: &str, args: &[&str]) -> Option<String> {
+    let name = std::iter::once(program).chain(args.iter().copied()).collect::<Vec<&str>>().join(" ");
+
+    context.executor.probe(&name, &mut || {
+        let output = Command::new(program).args(args).output().ok()?;
+
+        if output.status.success() {
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        } else {
+            None
+        }
+    })
+}
@@ -0,0 +1,104 @@
+use crate::core::{failable::Failable, hashes};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+pub struct Fingerprint {
+    pub outputs: Vec<PathBuf>,
+
+    hasher: Sha256,
+}
+
+impl Fingerprint {
+    pub fn new() -> Self {
+        Self {
+            outputs: Vec::new(),
+            hasher: Sha256::new(),
+        }
+    }
+
+    pub fn value(mut self, value: &str) -> Self {
+        // Each input is length prefixed so two different sequences of inputs can never produce the same stream of bytes.
+        self.hasher.update((value.len() as u64).to_le_bytes());
+        self.hasher.update(value.as_bytes());
+        self
+    }
+
+    pub fn file(self, path: &PathBuf) -> Failable<Self> {
+        if !path.is_file() {
+            return Ok(self.value(&format!("{:?}", path)).value("missing"));
+        }
+
+        let content_hash = hashes::sha256_file(path)?;
+        Ok(self.value(&format!("{:?}", path)).value(&content_hash))
+    }
+
+    pub fn dir(mut self, path: &PathBuf) -> Failable<Self> {
+        if !path.is_dir() {
+            return Ok(self.value(&format!("{:?}", path)).value("missing"));
+        }
+
+        // Directory entries are sorted so the fingerprint doesn't depend on the order the file system happens to list them in.
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(path)?.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+        entries.sort();
+
+        // Links inside the directory are never followed, only where they point counts. A link back up the tree would otherwise
+        // recurse forever, and a linked directory is usually a build product (such as a symlinked output) rather than a source.
+        for entry in entries {
+            let file_type = std::fs::symlink_metadata(&entry)?.file_type();
+
+            self = if file_type.is_symlink() {
+                let link_target = std::fs::read_link(&entry)?;
+                self.value(&format!("{:?}", entry)).value(&format!("link to {:?}", link_target))
+            } else if file_type.is_dir() {
+                self.dir(&entry)?
+            } else {
+                self.file(&entry)?
+            };
+        }
+
+        Ok(self)
+    }
+
+    #[allow(clippy::ptr_arg)]
+    pub fn output(mut self, path: &PathBuf) -> Self {
+        self.outputs.push(path.clone());
+        self
+    }
+
+    pub fn finish(&self) -> String {
+        hashes::to_hex(&self.hasher.clone().finalize())
+    }
+}
+
+impl Default for Fingerprint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn dir_does_not_follow_links() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().join("dir");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested").join("file.txt"), "content").unwrap();
+
+        // A link back up the tree would never finish if it was followed.
+        std::os::unix::fs::symlink(&dir, dir.join("nested").join("loop")).unwrap();
+        let before = Fingerprint::new().dir(&dir).unwrap().finish();
+
+        // Only where a link points is part of the fingerprint, not what it points at.
+        std::fs::write(dir.join("nested").join("file.txt"), "changed").unwrap();
+        let changed_file = Fingerprint::new().dir(&dir).unwrap().finish();
+        assert_ne!(before, changed_file);
+
+        std::fs::remove_file(dir.join("nested").join("loop")).unwrap();
+        std::os::unix::fs::symlink(temp_dir.path(), dir.join("nested").join("loop")).unwrap();
+        assert_ne!(changed_file, Fingerprint::new().dir(&dir).unwrap().finish());
+    }
+}
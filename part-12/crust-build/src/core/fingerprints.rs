@@ -0,0 +1,39 @@
+use crate::{
+    core::{build_report, context::Context, failable_unit::FailableUnit, fingerprint::Fingerprint, io, logs},
+    log_tag,
+};
+use std::{path::PathBuf, time::Instant};
+
+fn fingerprint_path(context: &Context, step: &str) -> PathBuf {
+    context.working_dir.join("fingerprints").join(step)
+}
+
+pub fn run_step(
+    context: &Context,
+    step: &str,
+    fingerprint: &Fingerprint,
+    work: &mut dyn FnMut() -> FailableUnit,
+) -> FailableUnit {
+    let fingerprint_path = fingerprint_path(context, step);
+    let current = fingerprint.finish();
+
+    // A step can only be skipped if its inputs haven't changed since it last succeeded and everything it produced is still there.
+    let unchanged = fingerprint_path.exists() && io::read_string(&fingerprint_path)? == current;
+    let outputs_exist = fingerprint.outputs.iter().all(|output| output.exists());
+
+    if !context.force && unchanged && outputs_exist {
+        logs::out(log_tag!(), &format!("Step '{}' is up to date, skipping.", step));
+        build_report::record_step(context, step, true, Default::default());
+        return Ok(());
+    }
+
+    // The old fingerprint is removed first so a failed or interrupted step is never mistaken for a successful one.
+    io::delete(context, &fingerprint_path)?;
+
+    let started = Instant::now();
+    let result = work();
+    build_report::record_step(context, step, false, started.elapsed());
+    result?;
+
+    io::write_string(context, &current, &fingerprint_path)
+}
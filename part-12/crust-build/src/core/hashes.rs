@@ -0,0 +1,19 @@
+use crate::core::failable::Failable;
+use sha2::{Digest, Sha256};
+use std::{fs::File, path::PathBuf};
+
+pub fn sha256_bytes(content: &[u8]) -> String {
+    to_hex(&Sha256::digest(content))
+}
+
+pub fn sha256_file(path: &PathBuf) -> Failable<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
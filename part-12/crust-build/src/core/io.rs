@@ -1,145 +1,551 @@
 use crate::{
-    core::{failable::Failable, failable_unit::FailableUnit, logs, script::Script, scripts},
+    core::{context::Context, failable::Failable, failable_unit::FailableUnit, logs},
     log_tag,
 };
 use std::{
     fs::File,
-    io::Write,
+    io::{Read, Write},
     path::{Path, PathBuf},
 };
 
-pub fn create_symlink(source: &PathBuf, target: &PathBuf, working_dir: &PathBuf) -> FailableUnit {
-    delete(target)?;
+pub enum ArchiveType {
+    Zip,
+    TarGz,
+    TarXz,
+}
+
+impl ArchiveType {
+    // The file's leading bytes are the most reliable signal, the name (for example a download url) is only a fallback.
+    pub fn detect(path: &PathBuf, name: &str) -> Failable<ArchiveType> {
+        let mut header = [0u8; 6];
+        let count = File::open(path)?.read(&mut header)?;
+
+        match &header[..count] {
+            [0x50, 0x4b, 0x03, 0x04, ..] | [0x50, 0x4b, 0x05, 0x06, ..] => return Ok(ArchiveType::Zip),
+            [0x1f, 0x8b, ..] => return Ok(ArchiveType::TarGz),
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00] => return Ok(ArchiveType::TarXz),
+            _ => {}
+        }
+
+        let name = name.to_lowercase();
+
+        if name.ends_with(".zip") {
+            Ok(ArchiveType::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(ArchiveType::TarGz)
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Ok(ArchiveType::TarXz)
+        } else {
+            Err(format!("Unrecognised archive type for '{}' at {:?}", name, path).into())
+        }
+    }
+}
+
+#[allow(clippy::ptr_arg)]
+pub fn create_symlink(context: &Context, source: &PathBuf, target: &PathBuf, working_dir: &PathBuf) -> FailableUnit {
+    // A relative target is placed inside the working dir, a relative source is left as is and so resolves against the link's own location.
+    let target = working_dir.join(target);
+
+    context.executor.file_operation("symlink", Some(source), &target, &mut || {
+        delete(context, &target)?;
+
+        logs::out(log_tag!(), &format!("Creating symlink: {:?} <=> {:?}", source, target));
+
+        symlink(source, &target)
+            .map_err(|error| format!("Failed to create symlink {:?} => {:?}: {}", target, source, error).into())
+    })
+}
+
+#[cfg(not(target_os = "windows"))]
+fn symlink(source: &Path, target: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(source, target)
+}
 
-    logs::out(log_tag!(), &format!("Creating symlink: {:?} <=> {:?}", source, target));
+#[cfg(target_os = "windows")]
+fn symlink(source: &Path, target: &Path) -> std::io::Result<()> {
+    let resolved_source = target.parent().map(|parent| parent.join(source)).unwrap_or_else(|| source.to_path_buf());
 
-    if cfg!(target_os = "windows") {
-        scripts::run(&Script::new(&format!("mklink /D {:?} {:?}", &target, &source)).working_dir(&working_dir))
-    } else {
-        scripts::run(&Script::new(&format!("ln -s {:?} {:?}", &source, &target)).working_dir(&working_dir))
+    // File symlinks have the same restrictions as directory ones, without them a hard link still shares the data when the
+    // source is on the same volume, otherwise it is copied.
+    if !resolved_source.is_dir() {
+        return std::os::windows::fs::symlink_file(source, target)
+            .or_else(|_| std::fs::hard_link(&resolved_source, target))
+            .or_else(|_| std::fs::copy(&resolved_source, target).map(|_| ()));
     }
+
+    // Directory symlinks need either admin rights or developer mode, whereas a junction works for any user but only for absolute paths.
+    std::os::windows::fs::symlink_dir(source, target).or_else(|_| create_junction(&resolved_source, target))
+}
+
+#[cfg(target_os = "windows")]
+mod kernel32 {
+    use std::ffi::c_void;
+
+    pub const GENERIC_WRITE: u32 = 0x4000_0000;
+    pub const OPEN_EXISTING: u32 = 3;
+    pub const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    pub const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x0020_0000;
+    pub const FSCTL_SET_REPARSE_POINT: u32 = 0x0009_00a4;
+    pub const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xa000_0003;
+    pub const INVALID_HANDLE_VALUE: isize = -1;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn CreateFileW(
+            file_name: *const u16,
+            desired_access: u32,
+            share_mode: u32,
+            security_attributes: *mut c_void,
+            creation_disposition: u32,
+            flags_and_attributes: u32,
+            template_file: *mut c_void,
+        ) -> *mut c_void;
+
+        pub fn DeviceIoControl(
+            device: *mut c_void,
+            io_control_code: u32,
+            in_buffer: *const c_void,
+            in_buffer_size: u32,
+            out_buffer: *mut c_void,
+            out_buffer_size: u32,
+            bytes_returned: *mut u32,
+            overlapped: *mut c_void,
+        ) -> i32;
+
+        pub fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+}
+
+// A junction is an empty directory carrying a mount point reparse point, the same thing 'mklink /J' creates.
+#[cfg(target_os = "windows")]
+fn create_junction(source: &Path, target: &Path) -> std::io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+
+    // The reparse point holds the NT form of the path ('\??\C:\...'), canonicalizing gives us the '\\?\C:\...' form of it.
+    let source = std::fs::canonicalize(source)?;
+    let source = source.to_string_lossy();
+    let print_name = source.strip_prefix(r"\\?\").unwrap_or(&source).to_owned();
+    let substitute_name = format!(r"\??\{}", &print_name);
+
+    let encode = |name: &str| -> Vec<u8> {
+        std::ffi::OsStr::new(name).encode_wide().chain(std::iter::once(0)).flat_map(|unit| unit.to_le_bytes()).collect()
+    };
+    let substitute_name = encode(&substitute_name);
+    let print_name = encode(&print_name);
+
+    // REPARSE_DATA_BUFFER: tag, data length and a reserved field, then the offsets and lengths (in bytes, without the
+    // terminating nul) of both names within the path buffer that follows.
+    let path_buffer_length = substitute_name.len() + print_name.len();
+    let mut buffer = Vec::with_capacity(16 + path_buffer_length);
+    buffer.extend_from_slice(&kernel32::IO_REPARSE_TAG_MOUNT_POINT.to_le_bytes());
+    buffer.extend_from_slice(&((8 + path_buffer_length) as u16).to_le_bytes());
+    buffer.extend_from_slice(&0u16.to_le_bytes());
+    buffer.extend_from_slice(&0u16.to_le_bytes());
+    buffer.extend_from_slice(&((substitute_name.len() - 2) as u16).to_le_bytes());
+    buffer.extend_from_slice(&(substitute_name.len() as u16).to_le_bytes());
+    buffer.extend_from_slice(&((print_name.len() - 2) as u16).to_le_bytes());
+    buffer.extend_from_slice(&substitute_name);
+    buffer.extend_from_slice(&print_name);
+
+    std::fs::create_dir(target)?;
+
+    let target_name: Vec<u16> = target.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+
+    let result = unsafe {
+        let handle = kernel32::CreateFileW(
+            target_name.as_ptr(),
+            kernel32::GENERIC_WRITE,
+            0,
+            std::ptr::null_mut(),
+            kernel32::OPEN_EXISTING,
+            kernel32::FILE_FLAG_BACKUP_SEMANTICS | kernel32::FILE_FLAG_OPEN_REPARSE_POINT,
+            std::ptr::null_mut(),
+        );
+
+        if handle as isize == kernel32::INVALID_HANDLE_VALUE {
+            Err(std::io::Error::last_os_error())
+        } else {
+            let mut bytes_returned = 0u32;
+            let succeeded = kernel32::DeviceIoControl(
+                handle,
+                kernel32::FSCTL_SET_REPARSE_POINT,
+                buffer.as_ptr() as *const _,
+                buffer.len() as u32,
+                std::ptr::null_mut(),
+                0,
+                &mut bytes_returned,
+                std::ptr::null_mut(),
+            ) != 0;
+            let error = std::io::Error::last_os_error();
+            kernel32::CloseHandle(handle);
+
+            if succeeded {
+                Ok(())
+            } else {
+                Err(error)
+            }
+        }
+    };
+
+    // Don't leave an ordinary empty directory behind where the junction should have been.
+    if result.is_err() {
+        let _ = std::fs::remove_dir(target);
+    }
+
+    result
 }
 
 pub fn in_temp_dir(work: &mut dyn FnMut(&Path) -> FailableUnit) -> FailableUnit {
     let temp = tempfile::Builder::new().prefix("crust").tempdir()?;
-    work(&temp.path())
+    work(temp.path())
 }
 
-pub fn write_bytes(content: &[u8], path: &PathBuf) -> FailableUnit {
-    let parent = path.parent().ok_or("Parent directory not found")?;
-    std::fs::create_dir_all(parent)?;
+pub fn write_bytes(context: &Context, content: &[u8], path: &PathBuf) -> FailableUnit {
+    context.executor.file_operation("write", None, path, &mut || {
+        let parent = path.parent().ok_or("Parent directory not found")?;
+        std::fs::create_dir_all(parent)?;
 
-    let mut file = File::create(path)?;
-    file.write_all(content)?;
+        let mut file = File::create(path)?;
+        file.write_all(content)?;
 
-    Ok(())
+        Ok(())
+    })
 }
 
 pub fn read_string(path: &PathBuf) -> Failable<String> {
-    Ok(std::fs::read_to_string(&path)?)
+    Ok(std::fs::read_to_string(path)?)
 }
 
-pub fn write_string(content: &str, path: &PathBuf) -> FailableUnit {
-    write_bytes(content.as_bytes(), path)?;
+pub fn write_string(context: &Context, content: &str, path: &PathBuf) -> FailableUnit {
+    write_bytes(context, content.as_bytes(), path)?;
     Ok(())
 }
 
 #[cfg(target_os = "windows")]
-pub fn apply_permissions(_: &PathBuf, _: u32) -> FailableUnit {
+pub fn read_permissions(_: &PathBuf) -> Failable<u32> {
+    // Windows has no Unix permissions so we report a regular readable file.
+    Ok(0o644)
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn read_permissions(path: &PathBuf) -> Failable<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(std::fs::metadata(path)?.permissions().mode())
+}
+
+#[cfg(target_os = "windows")]
+pub fn apply_permissions(_: &Context, _: &PathBuf, _: u32) -> FailableUnit {
     // On Windows this is a no-op but we will leave it stubbed so we can cross compile easily.
     Ok(())
 }
 
 #[cfg(not(target_os = "windows"))]
-pub fn apply_permissions(path: &PathBuf, permissions: u32) -> FailableUnit {
+pub fn apply_permissions(context: &Context, path: &PathBuf, permissions: u32) -> FailableUnit {
     use std::os::unix::fs::PermissionsExt;
-    Ok(std::fs::set_permissions(&path, std::fs::Permissions::from_mode(permissions))?)
+
+    context.executor.file_operation(&format!("set_permissions {:o}", permissions), None, path, &mut || {
+        Ok(std::fs::set_permissions(path, std::fs::Permissions::from_mode(permissions))?)
+    })
 }
 
-pub fn copy(source: &PathBuf, destination: &PathBuf) -> FailableUnit {
-    if source.is_dir() {
-        if cfg!(target_os = "windows") {
-            scripts::run(&Script::new(&format!(
-                "xcopy /E /H /I {:?} {:?}",
-                &source,
-                &destination.join(source.file_name().ok_or("Missing file name")?)
-            )))?;
+// Behaves like 'cp -R': a directory copied onto an existing directory ends up inside it, otherwise the destination becomes the copy.
+pub fn copy(context: &Context, source: &PathBuf, destination: &PathBuf) -> FailableUnit {
+    context.executor.file_operation("copy", Some(source), destination, &mut || {
+        let destination = if source.is_dir() && destination.is_dir() {
+            destination.join(source.file_name().ok_or("Missing file name")?)
         } else {
-            scripts::run(&Script::new(&format!("cp -R {:?} {:?}", &source, &destination)))?;
+            destination.clone()
+        };
+
+        copy_tree(source, &destination)
+            .map_err(|error| format!("Failed to copy {:?} => {:?}: {}", source, destination, error).into())
+    })
+}
+
+// Unlike 'copy_dir_contents' symlinks are recreated rather than followed, and permissions are carried over.
+fn copy_tree(source: &Path, destination: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(source)?;
+
+    if metadata.file_type().is_symlink() {
+        if std::fs::symlink_metadata(destination).is_ok() {
+            std::fs::remove_file(destination)?;
         }
-    } else {
+        return symlink(&std::fs::read_link(source)?, destination);
+    }
+
+    if !metadata.is_dir() {
+        // The standard library copy already keeps the permission bits of the source file.
         std::fs::copy(source, destination)?;
+        return Ok(());
     }
 
-    Ok(())
+    std::fs::create_dir_all(destination)?;
+
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        copy_tree(&entry.path(), &destination.join(entry.file_name()))?;
+    }
+
+    // Applied last so a read only source directory doesn't stop us filling in its copy.
+    std::fs::set_permissions(destination, metadata.permissions())
 }
 
-pub fn create_dir(path: &PathBuf) -> FailableUnit {
-    std::fs::create_dir_all(path)?;
+pub fn create_dir(context: &Context, path: &PathBuf) -> FailableUnit {
+    context.executor.file_operation("create_dir", None, path, &mut || {
+        std::fs::create_dir_all(path)?;
 
-    Ok(())
+        Ok(())
+    })
 }
 
-pub fn delete(victim: &PathBuf) -> FailableUnit {
-    logs::out(log_tag!(), &format!("Deleting {:?}", victim));
+pub fn delete(context: &Context, victim: &PathBuf) -> FailableUnit {
+    context.executor.file_operation("delete", None, victim, &mut || {
+        // We need to also check if we are trying to delete a symlink by querying for link meta data.
+        // The regular `.exists` method will return false even if the victim is a broken symlink.
+        let is_symlink = std::fs::read_link(victim).is_ok();
 
-    // We need to also check if we are trying to delete a symlink by querying for link meta data.
-    // The regular `.exists` method will return false even if the victim is a broken symlink.
-    let is_symlink = std::fs::read_link(&victim).is_ok();
+        if !is_symlink && !victim.exists() {
+            return Ok(());
+        }
 
-    if !is_symlink && !victim.exists() {
-        return Ok(());
-    }
+        logs::out(log_tag!(), &format!("Deleting {:?}", victim));
 
-    if victim.is_dir() || is_symlink {
-        std::fs::remove_dir_all(victim)?;
-    } else {
-        std::fs::remove_file(victim)?;
-    }
+        if victim.is_dir() || is_symlink {
+            std::fs::remove_dir_all(victim)?;
+        } else {
+            std::fs::remove_file(victim)?;
+        }
 
-    Ok(())
+        Ok(())
+    })
 }
 
-pub fn rename(source: &PathBuf, destination: &PathBuf) -> FailableUnit {
-    logs::out(log_tag!(), &format!("Renaming: {:?} => {:?}", source, destination));
+pub fn rename(context: &Context, source: &PathBuf, destination: &PathBuf) -> FailableUnit {
+    context.executor.file_operation("rename", Some(source), destination, &mut || {
+        logs::out(log_tag!(), &format!("Renaming: {:?} => {:?}", source, destination));
 
-    std::fs::rename(source, destination)?;
-    Ok(())
+        std::fs::rename(source, destination)
+            .map_err(|error| format!("Failed to rename {:?} => {:?}: {}", source, destination, error).into())
+    })
 }
 
-pub fn unzip(source: &PathBuf, destination: &PathBuf) -> FailableUnit {
-    logs::out(log_tag!(), &format!("Unzipping: {:?} => {:?}", source, destination));
+pub fn unzip(context: &Context, source: &PathBuf, destination: &PathBuf) -> FailableUnit {
+    context.executor.file_operation("unzip", Some(source), destination, &mut || {
+        logs::out(log_tag!(), &format!("Unzipping: {:?} => {:?}", source, destination));
 
-    create_dir(destination)?;
+        create_dir(context, destination)?;
 
-    let zip_file = File::open(&source)?;
-    let mut archive = zip::ZipArchive::new(zip_file)?;
+        let zip_file = File::open(source)?;
+        let mut archive = zip::ZipArchive::new(zip_file)?;
 
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let outpath = match file.enclosed_name() {
-            Some(path) => destination.join(path.to_owned()),
-            None => continue,
-        };
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            let outpath = match file.enclosed_name() {
+                Some(path) => destination.join(path),
+                None => continue,
+            };
 
-        if (&*file.name()).ends_with('/') {
-            create_dir(&outpath)?;
-        } else {
-            if let Some(parent) = outpath.parent() {
-                if !parent.exists() {
-                    create_dir(&parent.to_path_buf())?;
+            if file.name().ends_with('/') {
+                create_dir(context, &outpath)?;
+            } else {
+                if let Some(parent) = outpath.parent() {
+                    if !parent.exists() {
+                        create_dir(context, &parent.to_path_buf())?;
+                    }
                 }
+
+                let mut outfile = File::create(&outpath)?;
+                std::io::copy(&mut file, &mut outfile)?;
             }
 
-            let mut outfile = File::create(&outpath)?;
-            std::io::copy(&mut file, &mut outfile)?;
+            // If the file has any Unix permissions we want to retain them, on Windows this would be a no-op.
+            if let Some(mode) = file.unix_mode() {
+                apply_permissions(context, &outpath, mode)?;
+            }
         }
 
-        // If the file has any Unix permissions we want to retain them, on Windows this would be a no-op.
-        if let Some(mode) = file.unix_mode() {
-            apply_permissions(&outpath, mode)?;
+        Ok(())
+    })
+}
+
+pub fn untar(context: &Context, source: &PathBuf, destination: &PathBuf, archive_type: &ArchiveType) -> FailableUnit {
+    context.executor.file_operation("untar", Some(source), destination, &mut || {
+        logs::out(log_tag!(), &format!("Extracting: {:?} => {:?}", source, destination));
+
+        create_dir(context, destination)?;
+
+        let file = File::open(source)?;
+        let decoder: Box<dyn Read> = match archive_type {
+            ArchiveType::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+            ArchiveType::TarXz => Box::new(xz2::read::XzDecoder::new(file)),
+            ArchiveType::Zip => return Err(format!("Not a tar archive: {:?}", source).into()),
+        };
+
+        let mut archive = tar::Archive::new(decoder);
+        archive.set_preserve_permissions(true);
+        archive.unpack(destination)?;
+
+        Ok(())
+    })
+}
+
+pub fn extract(context: &Context, source: &PathBuf, destination: &PathBuf, name: &str) -> FailableUnit {
+    match ArchiveType::detect(source, name)? {
+        ArchiveType::Zip => unzip(context, source, destination),
+        archive_type => untar(context, source, destination, &archive_type),
+    }
+}
+
+pub fn zip_dir(context: &Context, source_dir: &PathBuf, destination: &PathBuf) -> FailableUnit {
+    context.executor.file_operation("zip", Some(source_dir), destination, &mut || {
+        logs::out(log_tag!(), &format!("Zipping: {:?} => {:?}", source_dir, destination));
+
+        create_dir(context, &destination.parent().ok_or("Parent directory not found")?.to_path_buf())?;
+
+        // Entries are nested inside a folder named after the source dir, so extracting the archive doesn't scatter files around.
+        let root_name = source_dir.file_name().ok_or("Missing file name")?.to_string_lossy();
+        let mut archive = zip::ZipWriter::new(File::create(destination)?);
+        archive.add_directory(format!("{}/", root_name), zip::write::FileOptions::default())?;
+        zip_dir_entries(&mut archive, source_dir, &format!("{}/", root_name))?;
+        archive.finish()?;
+
+        Ok(())
+    })
+}
+
+fn zip_dir_entries(archive: &mut zip::ZipWriter<File>, dir: &PathBuf, prefix: &str) -> FailableUnit {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    entries.sort();
+
+    for entry in entries {
+        let name = format!("{}{}", prefix, entry.file_name().ok_or("Missing file name")?.to_string_lossy());
+        let options = zip::write::FileOptions::default().unix_permissions(read_permissions(&entry)?);
+
+        // Note that symlinks are followed (for example a debug build's 'assets' symlink) so the archive is always self contained.
+        if entry.is_dir() {
+            archive.add_directory(format!("{}/", name), options)?;
+            zip_dir_entries(archive, &entry, &format!("{}/", name))?;
+        } else {
+            archive.start_file(name, options)?;
+            std::io::copy(&mut File::open(&entry)?, archive)?;
         }
     }
 
     Ok(())
 }
+
+pub fn tar_gz_dir(context: &Context, source_dir: &PathBuf, destination: &PathBuf) -> FailableUnit {
+    context.executor.file_operation("tar_gz", Some(source_dir), destination, &mut || {
+        logs::out(log_tag!(), &format!("Archiving: {:?} => {:?}", source_dir, destination));
+
+        create_dir(context, &destination.parent().ok_or("Parent directory not found")?.to_path_buf())?;
+
+        let encoder = flate2::write::GzEncoder::new(File::create(destination)?, flate2::Compression::default());
+        let mut archive = tar::Builder::new(encoder);
+
+        // Like zip archives, symlinks are followed and everything is nested inside a folder named after the source dir.
+        archive.follow_symlinks(true);
+        archive.append_dir_all(source_dir.file_name().ok_or("Missing file name")?, source_dir)?;
+        archive.into_inner()?.finish()?;
+
+        Ok(())
+    })
+}
+
+pub fn copy_dir_contents(context: &Context, source_dir: &PathBuf, destination_dir: &PathBuf) -> FailableUnit {
+    context.executor.file_operation("copy_contents", Some(source_dir), destination_dir, &mut || {
+        std::fs::create_dir_all(destination_dir)?;
+
+        // Symlinks are resolved so the copy is self contained, for example the 'assets' symlink in a debug build's output.
+        for entry in std::fs::read_dir(source_dir)? {
+            let source = entry?.path();
+            let destination = destination_dir.join(source.file_name().ok_or("Missing file name")?);
+
+            if source.is_dir() {
+                copy_dir_contents(context, &source, &destination)?;
+            } else {
+                std::fs::copy(&source, &destination)
+                    .map_err(|error| format!("Failed to copy {:?} => {:?}: {}", source, destination, error))?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+pub fn list_files(dir: &PathBuf) -> Failable<Vec<PathBuf>> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    entries.sort();
+
+    let mut files = Vec::new();
+
+    for entry in entries {
+        if entry.is_dir() {
+            files.extend(list_files(&entry)?);
+        } else {
+            files.push(entry);
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn copy_tree_recreates_links() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(source.join("lib")).unwrap();
+        std::fs::write(source.join("lib").join("libSDL2-2.0.so.0.14.0"), "library").unwrap();
+
+        // Shared library version links are relative, and must stay links rather than becoming copies of what they point at.
+        std::os::unix::fs::symlink("libSDL2-2.0.so.0.14.0", source.join("lib").join("libSDL2-2.0.so.0")).unwrap();
+        std::os::unix::fs::symlink("missing", source.join("lib").join("dangling")).unwrap();
+
+        let destination = temp_dir.path().join("destination");
+        copy_tree(&source, &destination).unwrap();
+
+        let link = destination.join("lib").join("libSDL2-2.0.so.0");
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_link(&link).unwrap(), PathBuf::from("libSDL2-2.0.so.0.14.0"));
+        assert_eq!(std::fs::read_to_string(&link).unwrap(), "library");
+        assert_eq!(std::fs::read_link(destination.join("lib").join("dangling")).unwrap(), PathBuf::from("missing"));
+
+        // Copying over an earlier copy replaces the links rather than failing on them.
+        copy_tree(&source, &destination).unwrap();
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn copy_tree_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(source.join("read-only")).unwrap();
+        std::fs::write(source.join("binary"), "binary").unwrap();
+        std::fs::write(source.join("read-only").join("data.txt"), "data").unwrap();
+        std::fs::set_permissions(source.join("binary"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::set_permissions(source.join("read-only"), std::fs::Permissions::from_mode(0o555)).unwrap();
+
+        let destination = temp_dir.path().join("destination");
+        let result = copy_tree(&source, &destination);
+        let mode = |path: &PathBuf| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        let binary_mode = mode(&destination.join("binary"));
+        let read_only_mode = mode(&destination.join("read-only"));
+
+        // The temp dir can't clean up after itself while the directories are read only.
+        std::fs::set_permissions(source.join("read-only"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::set_permissions(destination.join("read-only"), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        result.unwrap();
+        assert_eq!(binary_mode, 0o755);
+        assert_eq!(read_only_mode, 0o555);
+        assert_eq!(std::fs::read_to_string(destination.join("read-only").join("data.txt")).unwrap(), "data");
+    }
+}
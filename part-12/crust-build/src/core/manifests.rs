@@ -1,35 +1,452 @@
 use crate::{
     core::{
-        failable_unit::FailableUnit,
-        {context::Context, io, logs},
+        context::Context, failable::Failable, failable_unit::FailableUnit, fingerprint::Fingerprint, fingerprints, io, logs,
+        script::Script, scripts,
     },
     log_tag,
 };
+use std::path::{Path, PathBuf};
+
+// Every table that can declare dependencies, target specific ones ('[target.<cfg>.dependencies]') are found separately.
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
+// Package fields which hold a path, and so have to be rebased like any other path when the manifest moves.
+const PACKAGE_PATH_FIELDS: &[&str] = &["build", "readme", "license-file"];
+
+// Target tables other than '[lib]' and '[[bin]]', with the directory Cargo looks in when one of them has no 'path'.
+const OTHER_TARGET_TABLES: &[(&str, &str)] = &[("example", "examples"), ("test", "tests"), ("bench", "benches")];
 
 pub fn create(context: &Context, crate_type: &str) -> FailableUnit {
+    let source_manifest_path = context.source_dir.join("Cargo.toml");
+    let manifest_path = context.working_dir.join("Cargo.toml");
+    let workspace_manifest_path = find_workspace_manifest(&context.source_dir)?;
+
+    let mut fingerprint = Fingerprint::new()
+        .value(crate_type)
+        .value(&format!("{:?}", &context.source_dir))
+        .file(&source_manifest_path)?
+        .output(&manifest_path);
+
+    if let Some(workspace_manifest_path) = &workspace_manifest_path {
+        fingerprint = fingerprint.file(workspace_manifest_path)?;
+    }
+
+    fingerprints::run_step(context, "cargo-manifest", &fingerprint, &mut || {
+        write_manifest(context, crate_type, &workspace_manifest_path)?;
+        validate(context, crate_type, &manifest_path)
+    })
+}
+
+fn write_manifest(context: &Context, crate_type: &str, workspace_manifest_path: &Option<PathBuf>) -> FailableUnit {
     logs::out(log_tag!(), "Creating custom Cargo.toml manifest ...");
 
-    let main_source_dir = context.source_dir.join("src");
     let manifest_content = io::read_string(&context.source_dir.join("Cargo.toml"))?;
     let mut manifest = manifest_content.parse::<toml_edit::Document>()?;
 
-    let lib_src = format!("{:?}", &main_source_dir.join("lib.rs"));
-    manifest["lib"]["path"] = toml_edit::value(lib_src);
+    // The generated manifest lives somewhere else, so anything pointing back at the crate's sources has to become absolute.
+    rebase_paths(&mut manifest, &context.source_dir);
+
+    if let Some(workspace_manifest_path) = workspace_manifest_path {
+        inherit_workspace(&mut manifest, workspace_manifest_path, &context.source_dir.join("Cargo.toml"))?;
+    }
+
+    set_targets(context, &mut manifest, crate_type)?;
+
+    // The copy is the root of its own workspace: the original workspace doesn't list it as a member and any workspace above
+    // the working dir has nothing to do with it. Whatever it inherited from the original workspace has been filled in above.
+    if let Some(package) = manifest["package"].as_table_mut() {
+        package.remove("workspace");
+    }
+    manifest["workspace"] = toml_edit::table();
+
+    io::write_string(context, &manifest.to_string(), &context.working_dir.join("Cargo.toml"))
+}
+
+// A field of the crate's '[package]', following '{ workspace = true }' to the workspace's '[workspace.package]' like Cargo does.
+pub fn package_field(source_dir: &PathBuf, field: &str) -> Failable<toml_edit::Item> {
+    let manifest = io::read_string(&source_dir.join("Cargo.toml"))?.parse::<toml_edit::Document>()?;
+    let value = lookup(manifest.as_item(), &["package", field]);
+
+    if lookup(value, &["workspace"]).as_bool() != Some(true) {
+        return Ok(value.clone());
+    }
+
+    let workspace_manifest_path = find_workspace_manifest(source_dir)?
+        .ok_or_else(|| format!("Field 'package/{}' is inherited but {:?} is not part of a workspace", field, source_dir))?;
+    let workspace = io::read_string(&workspace_manifest_path)?.parse::<toml_edit::Document>()?;
+
+    Ok(lookup(workspace.as_item(), &["workspace", "package", field]).clone())
+}
+
+// Like Cargo we use the workspace the package names explicitly, otherwise the closest manifest above it with a '[workspace]'.
+#[allow(clippy::ptr_arg)]
+fn find_workspace_manifest(source_dir: &PathBuf) -> Failable<Option<PathBuf>> {
+    let manifest = io::read_string(&source_dir.join("Cargo.toml"))?.parse::<toml_edit::Document>()?;
+
+    if let Some(workspace_dir) = lookup(manifest.as_item(), &["package", "workspace"]).as_str() {
+        return Ok(Some(source_dir.join(workspace_dir).join("Cargo.toml")));
+    }
 
-    let crate_types = manifest["lib"]["crate-type"].as_array_mut().ok_or("Field 'lib/crate-type' not found in manifest!")?;
+    for dir in source_dir.ancestors() {
+        let candidate = dir.join("Cargo.toml");
 
-    for i in 0..crate_types.iter().count() {
-        crate_types.remove(i);
+        if candidate.exists() && io::read_string(&candidate)?.parse::<toml_edit::Document>()?.contains_table("workspace") {
+            return Ok(Some(candidate));
+        }
     }
 
-    crate_types.push(crate_type).map_err(|_| "Failed to set manifest crate-type")?;
+    Ok(None)
+}
+
+// The directory of every path dependency of the manifest, including path dependencies of path dependencies. Development
+// dependencies are left out as they never take part in building the library or binaries.
+#[allow(clippy::ptr_arg)]
+pub fn path_dependencies(manifest_path: &PathBuf) -> Failable<Vec<PathBuf>> {
+    let mut dependency_dirs: Vec<PathBuf> = Vec::new();
+    let mut pending = vec![manifest_path.clone()];
+
+    while let Some(manifest_path) = pending.pop() {
+        // The generated manifest doesn't exist yet on the very first build, or at all in a dry run.
+        if !manifest_path.exists() {
+            continue;
+        }
+
+        let manifest_dir = manifest_path.parent().ok_or("Parent directory not found")?;
+        let manifest = io::read_string(&manifest_path)?.parse::<toml_edit::Document>()?;
+        let mut tables = vec![manifest.as_item()];
+
+        for target in table_keys(lookup(manifest.as_item(), &["target"])) {
+            tables.push(lookup(manifest.as_item(), &["target", &target]));
+        }
+
+        let mut dependency_lists = Vec::new();
+
+        for table in tables {
+            dependency_lists.push(lookup(table, &["dependencies"]));
+            dependency_lists.push(lookup(table, &["build-dependencies"]));
+        }
 
-    let bin_src = format!("{:?}", &main_source_dir.join("bin.rs"));
-    manifest["bin"]
-        .as_array_of_tables_mut()
-        .ok_or("Missing 'bin' manifest entry")?
-        .get_mut(0)
-        .ok_or("Missing 'bin' manifest element 0")?["path"] = toml_edit::value(bin_src);
+        for registry in table_keys(lookup(manifest.as_item(), &["patch"])) {
+            dependency_lists.push(lookup(manifest.as_item(), &["patch", &registry]));
+        }
+
+        for dependencies in dependency_lists {
+            for name in table_keys(dependencies) {
+                if let Some(path) = lookup(dependencies, &[&name, "path"]).as_str() {
+                    // Relative paths of dependencies of dependencies go through '..', so the same one can be reached by different paths.
+                    let dependency_dir = PathBuf::from(absolute(manifest_dir, path));
+                    let dependency_dir = std::fs::canonicalize(&dependency_dir).unwrap_or(dependency_dir);
+
+                    if !dependency_dirs.contains(&dependency_dir) {
+                        pending.push(dependency_dir.join("Cargo.toml"));
+                        dependency_dirs.push(dependency_dir);
+                    }
+                }
+            }
+        }
+    }
+
+    dependency_dirs.sort();
+    Ok(dependency_dirs)
+}
+
+// Indexing a 'toml_edit::Item' panics on a missing key, this returns an empty item instead so optional keys can be chained.
+pub fn lookup<'a>(item: &'a toml_edit::Item, keys: &[&str]) -> &'a toml_edit::Item {
+    static MISSING: toml_edit::Item = toml_edit::Item::None;
+
+    keys.iter().try_fold(item, |item, key| item.get(key)).unwrap_or(&MISSING)
+}
+
+fn absolute(base_dir: &Path, path: &str) -> String {
+    if Path::new(path).is_absolute() {
+        path.to_owned()
+    } else {
+        base_dir.join(path).display().to_string()
+    }
+}
+
+fn rebase_path_field(item: &mut toml_edit::Item, field: &str, base_dir: &Path) {
+    if let Some(path) = lookup(item, &[field]).as_str().map(|path| absolute(base_dir, path)) {
+        item[field] = toml_edit::value(path);
+    }
+}
+
+fn table_keys(item: &toml_edit::Item) -> Vec<String> {
+    match item.as_table_like() {
+        Some(table) => table.iter().filter(|(_, value)| !value.is_none()).map(|(key, _)| key.to_owned()).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn rebase_dependencies(dependencies: &mut toml_edit::Item, base_dir: &Path) {
+    for name in table_keys(dependencies) {
+        if lookup(dependencies, &[&name]).is_table_like() {
+            rebase_path_field(&mut dependencies[name.as_str()], "path", base_dir);
+        }
+    }
+}
+
+// '[patch.<registry>]' and '[replace]' entries look just like dependencies, one level down for patches.
+fn rebase_overrides(manifest: &mut toml_edit::Document, base_dir: &Path) {
+    for registry in table_keys(lookup(manifest.as_item(), &["patch"])) {
+        rebase_dependencies(&mut manifest["patch"][registry.as_str()], base_dir);
+    }
+
+    rebase_dependencies(&mut manifest["replace"], base_dir);
+}
+
+fn rebase_paths(manifest: &mut toml_edit::Document, source_dir: &Path) {
+    if manifest.contains_table("package") {
+        for field in PACKAGE_PATH_FIELDS {
+            rebase_path_field(&mut manifest["package"], field, source_dir);
+        }
+
+        // A build script found by convention wouldn't be found next to the copy, so it is named explicitly.
+        if lookup(manifest.as_item(), &["package", "build"]).is_none() && source_dir.join("build.rs").exists() {
+            manifest["package"]["build"] = toml_edit::value(source_dir.join("build.rs").display().to_string());
+        }
+    }
+
+    for table in DEPENDENCY_TABLES {
+        rebase_dependencies(&mut manifest[*table], source_dir);
+    }
+
+    for target in table_keys(lookup(manifest.as_item(), &["target"])) {
+        for table in DEPENDENCY_TABLES {
+            rebase_dependencies(&mut manifest["target"][target.as_str()][*table], source_dir);
+        }
+    }
 
-    io::write_string(&manifest.to_string(), &context.working_dir.join("Cargo.toml"))
+    rebase_overrides(manifest, source_dir);
+
+    for (table, default_dir) in OTHER_TARGET_TABLES {
+        if let Some(targets) = manifest[*table].as_array_of_tables_mut() {
+            for i in 0..targets.len() {
+                if let Some(target) = targets.get_mut(i) {
+                    let path = match (
+                        target.get("path").and_then(|path| path.as_str()),
+                        target.get("name").and_then(|name| name.as_str()),
+                    ) {
+                        (Some(path), _) => absolute(source_dir, path),
+                        (None, Some(name)) => source_dir.join(default_dir).join(format!("{}.rs", name)).display().to_string(),
+                        (None, None) => continue,
+                    };
+
+                    target["path"] = toml_edit::value(path);
+                }
+            }
+        }
+    }
+}
+
+fn inherit_workspace(
+    manifest: &mut toml_edit::Document,
+    workspace_manifest_path: &PathBuf,
+    source_manifest_path: &Path,
+) -> FailableUnit {
+    let workspace_dir = workspace_manifest_path.parent().ok_or("Parent directory not found")?;
+    let mut workspace = io::read_string(workspace_manifest_path)?.parse::<toml_edit::Document>()?;
+
+    // Paths in the workspace manifest are relative to the workspace, not to the package.
+    rebase_dependencies(&mut workspace["workspace"]["dependencies"], workspace_dir);
+    rebase_overrides(&mut workspace, workspace_dir);
+
+    for field in table_keys(lookup(manifest.as_item(), &["package"])) {
+        if lookup(manifest.as_item(), &["package", &field, "workspace"]).as_bool() != Some(true) {
+            continue;
+        }
+
+        let mut inherited = lookup(workspace.as_item(), &["workspace", "package", &field]).clone();
+
+        if inherited.is_none() {
+            return Err(format!("Field 'package/{}' is inherited but not set in {:?}", field, workspace_manifest_path).into());
+        }
+
+        if let (true, Some(path)) = (PACKAGE_PATH_FIELDS.contains(&field.as_str()), inherited.as_str()) {
+            inherited = toml_edit::value(absolute(workspace_dir, path));
+        }
+
+        manifest["package"][field.as_str()] = inherited;
+
+        // A dotted 'field.workspace = true' leaves the key without the usual spacing once it is a plain value.
+        if let Some((mut key, _)) = manifest["package"].as_table_mut().and_then(|package| package.get_key_value_mut(&field)) {
+            key.fmt();
+        }
+    }
+
+    let declared = lookup(workspace.as_item(), &["workspace", "dependencies"]);
+
+    for table in DEPENDENCY_TABLES {
+        inherit_dependencies(&mut manifest[*table], declared, workspace_manifest_path)?;
+    }
+
+    for target in table_keys(lookup(manifest.as_item(), &["target"])) {
+        for table in DEPENDENCY_TABLES {
+            inherit_dependencies(&mut manifest["target"][target.as_str()][*table], declared, workspace_manifest_path)?;
+        }
+    }
+
+    // Patches and profiles only count in the workspace root, so a member has to bring along the ones it was built with.
+    if workspace_manifest_path != source_manifest_path {
+        for table in &["patch", "replace", "profile"] {
+            if workspace.contains_key(table) {
+                manifest[*table] = workspace[*table].clone();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn inherit_dependencies(
+    dependencies: &mut toml_edit::Item,
+    declared: &toml_edit::Item,
+    workspace_manifest_path: &PathBuf,
+) -> FailableUnit {
+    for name in table_keys(dependencies) {
+        if lookup(dependencies, &[&name, "workspace"]).as_bool() == Some(true) {
+            dependencies[name.as_str()] = inherit_dependency(lookup(dependencies, &[&name]), lookup(declared, &[&name]))
+                .ok_or_else(|| format!("Dependency '{}' is inherited but not declared in {:?}", name, workspace_manifest_path))?;
+        }
+    }
+
+    Ok(())
+}
+
+// A dependency written as 'name = { workspace = true, ... }' starts from the workspace's declaration, the member can only add to it.
+fn inherit_dependency(member: &toml_edit::Item, declared: &toml_edit::Item) -> Option<toml_edit::Item> {
+    let mut dependency = toml_edit::InlineTable::default();
+
+    if let Some(version) = declared.as_str() {
+        dependency.get_or_insert("version", version);
+    } else {
+        let declared = declared.as_table_like()?;
+        for (key, value) in declared.iter() {
+            if let Some(value) = value.as_value() {
+                dependency.get_or_insert(key, value.clone());
+            }
+        }
+    }
+
+    for (key, value) in member.as_table_like()?.iter() {
+        let value = match (key, value.as_value()) {
+            ("workspace", _) | (_, None) => continue,
+            (_, Some(value)) => value,
+        };
+
+        if key == "features" {
+            let features = dependency.get_or_insert("features", toml_edit::Array::default()).as_array_mut()?;
+            for feature in value.as_array()?.iter() {
+                if !features.iter().any(|existing| existing.as_str() == feature.as_str()) {
+                    features.push(feature.clone());
+                }
+            }
+        } else {
+            dependency.remove(key);
+            dependency.get_or_insert(key, value.clone());
+        }
+    }
+
+    dependency.fmt();
+    Some(toml_edit::value(dependency))
+}
+
+fn set_targets(context: &Context, manifest: &mut toml_edit::Document, crate_type: &str) -> FailableUnit {
+    let mut crate_types = toml_edit::Array::default();
+    crate_types.push(crate_type);
+
+    manifest["lib"]["path"] = toml_edit::value(context.package.library()?.path.display().to_string());
+    manifest["lib"]["crate-type"] = toml_edit::value(crate_types);
+
+    if !manifest.contains_key("bin") {
+        manifest["bin"] = toml_edit::Item::ArrayOfTables(toml_edit::ArrayOfTables::new());
+    }
+
+    let bins = manifest["bin"].as_array_of_tables_mut().ok_or("Manifest entry 'bin' is not an array of tables")?;
+
+    // Binaries Cargo found by convention rather than from a '[[bin]]' entry have to be spelled out, as there are no sources next to the copy.
+    for binary in &context.package.binaries {
+        let bin_src = toml_edit::value(binary.path.display().to_string());
+
+        match (0..bins.len()).find(|i| {
+            bins.get(*i).map_or(false, |bin| bin.get("name").and_then(|name| name.as_str()) == Some(binary.name.as_str()))
+        }) {
+            Some(i) => bins.get_mut(i).ok_or("Missing 'bin' manifest element")?["path"] = bin_src,
+            None => {
+                let mut bin = toml_edit::Table::new();
+                bin["name"] = toml_edit::value(binary.name.as_str());
+                bin["path"] = bin_src;
+                bins.push(bin);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Cargo itself is the judge of whether the generated manifest makes sense, so mistakes show up here rather than halfway through a compile.
+fn validate(context: &Context, crate_type: &str, manifest_path: &PathBuf) -> FailableUnit {
+    let script =
+        Script::new(&format!("cargo metadata --format-version 1 --no-deps --offline --manifest-path {:?}", manifest_path))
+            .working_dir(&context.working_dir)
+            .quiet();
+    let output = scripts::run_captured(context, &script)
+        .map_err(|error| format!("Generated manifest {:?} is invalid:\n{}", manifest_path, error))?;
+
+    // A dry run only records the script, there is no metadata to inspect.
+    if context.executor.is_recording() {
+        return Ok(());
+    }
+
+    let metadata: serde_json::Value = serde_json::from_str(&output.stdout)?;
+    let package = &metadata["packages"][0];
+
+    let has_library = package["targets"].as_array().map_or(false, |targets| {
+        targets.iter().any(|target| target["crate_types"].as_array().map_or(false, |types| types.iter().any(|t| t == crate_type)))
+    });
+
+    if !has_library {
+        return Err(format!("Generated manifest {:?} has no '{}' library target", manifest_path, crate_type).into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_path_dependencies_of_path_dependencies() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().to_path_buf();
+
+        let write_manifest = |dir: &str, content: &str| {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join("Cargo.toml"), content).unwrap();
+        };
+
+        write_manifest(
+            "app",
+            r#"
+                [dependencies]
+                engine = { path = "../engine" }
+                serde = "1.0"
+
+                [target.'cfg(unix)'.build-dependencies]
+                codegen = { path = "../codegen" }
+
+                [dev-dependencies]
+                testing = { path = "../testing" }
+            "#,
+        );
+        write_manifest("engine", "[dependencies]\nmaths = { path = \"../maths\" }\n");
+        write_manifest("codegen", "[dependencies]\nmaths = { path = \"../maths\" }\n");
+        write_manifest("maths", "[dependencies]\n");
+
+        let dependencies = path_dependencies(&root.join("app").join("Cargo.toml")).unwrap();
+        let names: Vec<String> = dependencies.iter().map(|dir| dir.file_name().unwrap().to_string_lossy().to_string()).collect();
+
+        assert_eq!(names, vec!["codegen", "engine", "maths"]);
+    }
 }
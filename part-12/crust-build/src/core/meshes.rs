@@ -0,0 +1,77 @@
+use crate::core::failable::Failable;
+use std::{collections::HashMap, io::BufReader, path::PathBuf};
+
+// The binary mesh format loaded by 'crust-main/src/core/io.rs', every number is little endian:
+// "CMSH", u32 version, u32 vertex count, u32 index count, u32 bytes per index (2 or 4),
+// then per vertex 3 f32 for the position and 2 f32 for the texture coordinate, then the indices.
+const MAGIC: &[u8; 4] = b"CMSH";
+const VERSION: u32 = 1;
+
+#[allow(clippy::ptr_arg)]
+pub fn is_mesh_source(path: &PathBuf) -> bool {
+    path.extension().map_or(false, |extension| extension == "obj")
+}
+
+pub fn convert_obj(path: &PathBuf, content: &[u8]) -> Failable<Vec<u8>> {
+    let (models, _) = tobj::load_obj_buf(&mut BufReader::new(content), true, |_| Ok((Vec::new(), HashMap::new())))
+        .map_err(|error| format!("Failed to load {:?}: {}", path, error))?;
+
+    let mut vertices: Vec<[f32; 5]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    // The application used to give every index its own vertex, identical vertices are shared instead which is where the
+    // format gets most of its size back.
+    let mut known_vertices: HashMap<[u32; 5], u32> = HashMap::new();
+
+    for model in &models {
+        let mesh = &model.mesh;
+
+        if mesh.texcoords.is_empty() {
+            return Err(format!("Model '{}' in {:?} has no texture coordinates", model.name, path).into());
+        }
+
+        for index in &mesh.indices {
+            let index = *index as usize;
+
+            // Texture coordinates are flipped the same way the application always flipped them when loading '.obj' files.
+            let vertex = [
+                mesh.positions[3 * index],
+                mesh.positions[3 * index + 1],
+                mesh.positions[3 * index + 2],
+                mesh.texcoords[2 * index],
+                -1. - mesh.texcoords[2 * index + 1],
+            ];
+
+            let next_index = vertices.len() as u32;
+            let vertex_index = *known_vertices.entry(vertex.map(f32::to_bits)).or_insert(next_index);
+
+            if vertex_index == next_index {
+                vertices.push(vertex);
+            }
+
+            indices.push(vertex_index);
+        }
+    }
+
+    let index_size: u32 = if vertices.len() <= u16::MAX as usize + 1 { 2 } else { 4 };
+    let mut data = Vec::with_capacity(20 + vertices.len() * 20 + indices.len() * index_size as usize);
+
+    data.extend_from_slice(MAGIC);
+    for value in &[VERSION, vertices.len() as u32, indices.len() as u32, index_size] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    for value in vertices.iter().flatten() {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    for index in &indices {
+        if index_size == 2 {
+            data.extend_from_slice(&(*index as u16).to_le_bytes());
+        } else {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+    }
+
+    Ok(data)
+}
@@ -1,13 +1,32 @@
+pub mod asset_pack;
+pub mod assets;
+pub mod build_plan;
+pub mod build_report;
+pub mod build_target;
+pub mod cargo_package;
+pub mod config;
 pub mod context;
+pub mod download_cache;
 pub mod downloads;
+pub mod executor;
 pub mod failable;
 pub mod failable_unit;
+pub mod fingerprint;
+pub mod fingerprints;
+pub mod hashes;
 pub mod io;
 pub mod logs;
 pub mod manifests;
+pub mod meshes;
 pub mod outputs;
+pub mod packages;
+pub mod profile;
 pub mod remote_zips;
 pub mod script;
 pub mod scripts;
+pub mod shaders;
 pub mod target;
+pub mod textures;
 pub mod variant;
+pub mod versions;
+pub mod web_server;
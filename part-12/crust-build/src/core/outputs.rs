@@ -1,29 +1,53 @@
 use crate::{
-    core::{context::Context, failable_unit::FailableUnit, io, logs},
+    core::{context::Context, failable_unit::FailableUnit, io, logs, script::Script, scripts, variant::Variant},
     log_tag,
 };
 use std::{path::PathBuf, vec::Vec};
 
+pub fn outputs_root_dir(context: &Context) -> PathBuf {
+    context.target_home_dir.join("out")
+}
+
 pub fn output_dir(context: &Context) -> PathBuf {
-    context.target_home_dir.join("out").join(context.variant.id())
+    outputs_root_dir(context).join(context.profile.id())
 }
 
 pub fn clean(context: &Context) -> FailableUnit {
-    io::delete(&output_dir(context))
+    io::delete(context, &output_dir(context))
 }
 
 pub fn collect(context: &Context, sources: Vec<PathBuf>) -> FailableUnit {
-    io::create_dir(&output_dir(context))?;
+    io::create_dir(context, &output_dir(context))?;
 
     for source in sources {
         logs::out(log_tag!(), &format!("Collecting: {:?}", source));
 
         if source.is_dir() {
-            io::copy(&source, &output_dir(context))?;
+            io::copy(context, &source, &output_dir(context))?;
         } else {
-            io::copy(&source, &output_dir(context).join(source.file_name().ok_or("Missing file name")?))?;
+            io::copy(context, &source, &output_dir(context).join(source.file_name().ok_or("Missing file name")?))?;
         }
     }
 
     Ok(())
 }
+
+pub fn launch(context: &Context, binary_file_name: &str) -> FailableUnit {
+    let binary_path = output_dir(context).join(binary_file_name);
+
+    logs::out(log_tag!(), &format!("Launching: {:?}", &binary_path));
+
+    scripts::run(context, &Script::new(&format!("{:?}", &binary_path)))
+}
+
+// Debug builds link to anything that doesn't change with the binary (the asset pack, third party libraries) so it is
+// never copied on each build, release builds get a copy so the output stands on its own.
+#[allow(clippy::ptr_arg)]
+pub fn link_or_collect(context: &Context, source: &PathBuf, name: &str) -> FailableUnit {
+    let output_dir = output_dir(context);
+
+    match context.variant {
+        Variant::Debug => io::create_symlink(context, source, &PathBuf::from(name), &output_dir),
+        Variant::Release => io::copy(context, source, &output_dir.join(name)),
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{
+    core::{
+        build_report, context::Context, failable::Failable, failable_unit::FailableUnit, hashes, io, logs, outputs, versions,
+    },
+    log_tag,
+};
+use serde::Serialize;
+use std::path::PathBuf;
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+#[derive(Serialize)]
+struct Manifest {
+    name: String,
+    version: String,
+    target: String,
+    variant: String,
+    profile: String,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize)]
+struct ManifestFile {
+    path: String,
+    size: u64,
+    sha256: String,
+}
+
+pub fn package_name(context: &Context) -> Failable<String> {
+    let binary_name = &context.binary_name;
+    let version = versions::cargo_version(context)?;
+
+    // Only the plain release profile goes without a suffix, so archives of other profiles can never be mistaken for it.
+    Ok(match context.profile.id() {
+        "release" => format!("{}-{}-{}", binary_name, version, context.target.id()),
+        profile => format!("{}-{}-{}-{}", binary_name, version, context.target.id(), profile),
+    })
+}
+
+pub fn stage(context: &Context) -> Failable<PathBuf> {
+    let output_dir = outputs::output_dir(context);
+
+    if !output_dir.exists() && !context.executor.is_recording() {
+        return Err(format!("Target '{}' has no output directory to package: {:?}", context.target.id(), &output_dir).into());
+    }
+
+    // Packages are assembled from a copy of the output so any packaging changes (such as bundled libraries) never leak back into it.
+    let stage_dir = context.working_dir.join("package").join(package_name(context)?);
+
+    logs::out(log_tag!(), &format!("Staging package: {:?}", &stage_dir));
+    io::delete(context, &stage_dir)?;
+    io::copy_dir_contents(context, &output_dir, &stage_dir)?;
+
+    // The build report describes the build machine rather than the application, so it is never shipped.
+    io::delete(context, &stage_dir.join(build_report::REPORT_FILE_NAME))?;
+
+    Ok(stage_dir)
+}
+
+pub fn archive(context: &Context, stage_dir: &PathBuf) -> Failable<Vec<PathBuf>> {
+    write_manifest(context, stage_dir)?;
+
+    let name = stage_dir.file_name().ok_or("Missing file name")?.to_string_lossy();
+    let zip_path = outputs::outputs_root_dir(context).join(format!("{}.zip", name));
+    let tar_gz_path = outputs::outputs_root_dir(context).join(format!("{}.tar.gz", name));
+
+    io::delete(context, &zip_path)?;
+    io::zip_dir(context, stage_dir, &zip_path)?;
+
+    io::delete(context, &tar_gz_path)?;
+    io::tar_gz_dir(context, stage_dir, &tar_gz_path)?;
+
+    logs::out(log_tag!(), &format!("Created packages: {:?}, {:?}", &zip_path, &tar_gz_path));
+
+    Ok(vec![zip_path, tar_gz_path])
+}
+
+fn write_manifest(context: &Context, stage_dir: &PathBuf) -> FailableUnit {
+    let manifest_path = stage_dir.join(MANIFEST_FILE_NAME);
+
+    if context.executor.is_recording() {
+        return io::write_string(context, "", &manifest_path);
+    }
+
+    io::delete(context, &manifest_path)?;
+
+    let mut files = Vec::new();
+
+    for file in io::list_files(stage_dir)? {
+        // Paths always use forward slashes so the manifest reads the same no matter which host produced it.
+        let relative_path =
+            file.strip_prefix(stage_dir)?.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>();
+
+        files.push(ManifestFile {
+            path: relative_path.join("/"),
+            size: std::fs::metadata(&file)?.len(),
+            sha256: hashes::sha256_file(&file)?,
+        });
+    }
+
+    let manifest = Manifest {
+        name: context.binary_name.clone(),
+        version: versions::cargo_version(context)?,
+        target: context.target.id().to_owned(),
+        variant: context.variant.id().to_owned(),
+        profile: context.profile.name.clone(),
+        files,
+    };
+
+    io::write_string(context, &serde_json::to_string_pretty(&manifest)?, &manifest_path)
+}
@@ -0,0 +1,96 @@
+use crate::core::{failable::Failable, io, manifests::lookup, variant::Variant};
+use std::path::PathBuf;
+
+const DEV: &str = "dev";
+const RELEASE: &str = "release";
+
+// A Cargo profile, either one of the built in ones or a custom '[profile.*]' section of the main Cargo.toml file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    // The built in profile this one ultimately inherits from, which decides debug or release behaviour on each platform.
+    pub variant: Variant,
+    pub debug_info: bool,
+}
+
+impl Profile {
+    pub fn of_variant(variant: Variant) -> Profile {
+        match variant {
+            Variant::Debug => Profile {
+                name: DEV.to_owned(),
+                variant,
+                debug_info: true,
+            },
+            Variant::Release => Profile {
+                name: RELEASE.to_owned(),
+                variant,
+                debug_info: false,
+            },
+        }
+    }
+
+    pub fn resolve(name: &str, manifest_path: &PathBuf) -> Failable<Profile> {
+        let manifest = io::read_string(manifest_path)?.parse::<toml_edit::Document>()?;
+
+        // Cargo itself calls the debug profile 'dev', but 'debug' is what we call it everywhere else so it is accepted too.
+        let name = if name == Variant::Debug.id() { DEV } else { name };
+
+        let mut chain = vec![name.to_owned()];
+        let mut debug_info = None;
+
+        loop {
+            let current = chain.last().ok_or("Missing profile")?.clone();
+            let section = lookup(manifest.as_item(), &["profile", &current]);
+
+            // The closest profile in the inheritance chain that says anything about debug info wins.
+            if debug_info.is_none() {
+                let debug = lookup(section, &["debug"]);
+                debug_info = debug.as_bool().or_else(|| debug.as_integer().map(|level| level > 0));
+            }
+
+            let variant = match current.as_str() {
+                DEV | "test" => Some(Variant::Debug),
+                RELEASE | "bench" => Some(Variant::Release),
+                _ => None,
+            };
+
+            if let Some(variant) = variant {
+                return Ok(Profile {
+                    name: name.to_owned(),
+                    variant,
+                    debug_info: debug_info.unwrap_or(variant == Variant::Debug),
+                });
+            }
+
+            let inherits = lookup(section, &["inherits"]).as_str().ok_or_else(|| {
+                format!(
+                    "Profile '{}' not found, add a '[profile.{}]' section with an 'inherits' key to {:?}",
+                    current, current, manifest_path
+                )
+            })?;
+
+            if chain.iter().any(|profile| profile == inherits) {
+                return Err(format!("Profile '{}' inherits from itself: {} -> {}", name, chain.join(" -> "), inherits).into());
+            }
+
+            chain.push(inherits.to_owned());
+        }
+    }
+
+    // The name of the directory Cargo writes this profile's output into, which we also use for our own output directories.
+    pub fn id(&self) -> &str {
+        match self.name.as_str() {
+            DEV | "test" => Variant::Debug.id(),
+            "bench" => Variant::Release.id(),
+            name => name,
+        }
+    }
+
+    pub fn rust_compiler_flag(&self) -> String {
+        match self.name.as_str() {
+            DEV => String::new(),
+            RELEASE => "--release".to_owned(),
+            name => format!("--profile {}", name),
+        }
+    }
+}
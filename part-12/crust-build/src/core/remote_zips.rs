@@ -1,28 +1,49 @@
 use crate::{
-    core::{downloads, failable_unit::FailableUnit, io, logs},
+    core::{build_report, context::Context, download_cache, failable_unit::FailableUnit, io, logs},
     log_tag,
 };
-use std::{fs::DirEntry, path::PathBuf};
+use std::path::PathBuf;
 
-pub fn fetch(url: &str, destination_dir_name: &str, destination_parent_dir: &PathBuf) -> FailableUnit {
+pub fn fetch(context: &Context, url: &str, destination_dir_name: &str, destination_parent_dir: &PathBuf) -> FailableUnit {
     let target_dir = destination_parent_dir.join(destination_dir_name);
+    build_report::record_third_party(context, destination_dir_name, &build_report::version_from_url(url), url);
 
     if target_dir.exists() {
         logs::out(log_tag!(), &format!("Destination already exists, skipping download: {:?}", &target_dir));
         return Ok(());
     }
 
-    io::in_temp_dir(&mut |temp_dir| {
-        let download_file_path = temp_dir.to_path_buf().join("download.zip");
-        downloads::download(&url, &download_file_path)?;
+    context
+        .executor
+        .download(url, &target_dir, &mut || download_and_extract(context, url, destination_dir_name, destination_parent_dir))
+}
 
+fn download_and_extract(
+    context: &Context,
+    url: &str,
+    destination_dir_name: &str,
+    destination_parent_dir: &PathBuf,
+) -> FailableUnit {
+    // The archive is resolved through the download cache which verifies its checksum and only reaches for the network if it has to.
+    let download_file_path = download_cache::resolve(context, url)?;
+
+    io::in_temp_dir(&mut |temp_dir| {
         let unzipped_dir = temp_dir.join("unzipped");
-        io::unzip(&download_file_path, &unzipped_dir)?;
 
-        // We will now massage the name of the unzipped directory to be whatever the caller specified. The directory to rename will be the first child of the 'unzipped' directory where we just unzipped the files.
-        let content_dir = unzipped_dir.join(&destination_dir_name);
-        io::rename(&std::fs::read_dir(&unzipped_dir)?.filter_map(|e| e.ok()).collect::<Vec<DirEntry>>()[0].path(), &content_dir)?;
-        io::create_dir(&destination_parent_dir)?;
-        io::copy(&content_dir, &destination_parent_dir)
+        // Cached archives are named after their checksum, so the url is passed along as a hint for the type of archive.
+        io::extract(context, &download_file_path, &unzipped_dir, url)?;
+
+        // We will now massage the name of the extracted content to be whatever the caller specified. Usually the archive holds a
+        // single top level directory which is renamed, though if it has several top level entries they are all kept together.
+        let content_dir = temp_dir.join(destination_dir_name);
+        let entries = std::fs::read_dir(&unzipped_dir)?.filter_map(|e| e.ok()).map(|e| e.path()).collect::<Vec<PathBuf>>();
+
+        match entries.as_slice() {
+            [single_dir] if single_dir.is_dir() => io::rename(context, single_dir, &content_dir)?,
+            _ => io::rename(context, &unzipped_dir, &content_dir)?,
+        }
+
+        io::create_dir(context, destination_parent_dir)?;
+        io::copy(context, &content_dir, destination_parent_dir)
     })
 }
@@ -1,9 +1,15 @@
-use std::{collections::HashMap, option::Option, path::PathBuf};
+use std::{collections::HashMap, option::Option, path::PathBuf, time::Duration};
 
+// The private unit field forces scripts to be created through 'Script::new' rather than marking the struct non exhaustive.
+#[allow(clippy::manual_non_exhaustive)]
 pub struct Script {
     pub content: String,
     pub working_dir: Option<PathBuf>,
     pub environment: HashMap<String, String>,
+    pub timeout: Option<Duration>,
+    pub retries: u32,
+    pub log_file: Option<PathBuf>,
+    pub quiet: bool,
 
     _private: (),
 }
@@ -14,6 +20,10 @@ impl Script {
             content: content.trim().to_owned(),
             environment: HashMap::new(),
             working_dir: Option::None,
+            timeout: Option::None,
+            retries: 0,
+            log_file: Option::None,
+            quiet: false,
             _private: (),
         }
     }
@@ -23,8 +33,34 @@ impl Script {
         self
     }
 
+    #[allow(clippy::ptr_arg)]
     pub fn working_dir(mut self, working_dir: &PathBuf) -> Self {
         self.working_dir = Some(working_dir.clone());
         self
     }
+
+    // The script is killed, along with anything it started, if it runs for longer than this.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    // Intended for steps that reach out to the network, each retry waits twice as long as the one before.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    // Everything the script prints is also appended to this file, which survives after the terminal output has scrolled away.
+    #[allow(clippy::ptr_arg)]
+    pub fn log_file(mut self, log_file: &PathBuf) -> Self {
+        self.log_file = Some(log_file.clone());
+        self
+    }
+
+    // For scripts whose output is data to be parsed rather than progress, it is still captured and logged but not printed.
+    pub fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
 }
@@ -1,9 +1,58 @@
-use crate::core::{failable::Failable, failable_unit::FailableUnit, io, script::Script};
+use crate::{
+    core::{build_report, context::Context, failable::Failable, failable_unit::FailableUnit, io, logs, script::Script},
+    log_tag,
+};
 use std::{
-    path::Path,
-    process::{Command, Stdio},
+    collections::VecDeque,
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
+// How many lines of output are included in the error when a script fails.
+const FAILURE_OUTPUT_LINES: usize = 20;
+
+// How long output is still read after the script has finished, see 'execute'.
+const OUTPUT_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+
+type SharedLog = Option<Arc<Mutex<std::fs::File>>>;
+type SharedTail = Arc<Mutex<VecDeque<String>>>;
+
+#[derive(Debug, Default)]
+pub struct ScriptOutput {
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// The message is printed as is for both Display and Debug so a failed script reads well in the 'Fatal error' output.
+struct ScriptError {
+    message: String,
+    exit_status: Option<i32>,
+}
+
+impl std::fmt::Debug for ScriptError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(&self.message)
+    }
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
 #[cfg(not(target_os = "windows"))]
 fn create_command(script_dir: &Path, script_block: &str) -> Failable<Command> {
     let mut script_content = "#!/usr/bin/env bash\n\n".to_owned();
@@ -13,8 +62,8 @@ fn create_command(script_dir: &Path, script_block: &str) -> Failable<Command> {
     script_content += script_block;
 
     let script_file_path = script_dir.join("script.sh");
-    io::write_string(&script_content, &script_file_path)?;
-    io::apply_permissions(&script_file_path, 0o755)?;
+    std::fs::write(&script_file_path, script_content)?;
+    std::fs::set_permissions(&script_file_path, std::os::unix::fs::PermissionsExt::from_mode(0o755))?;
 
     Ok(Command::new(&script_file_path))
 }
@@ -22,31 +71,314 @@ fn create_command(script_dir: &Path, script_block: &str) -> Failable<Command> {
 #[cfg(target_os = "windows")]
 fn create_command(script_dir: &Path, script_block: &str) -> Failable<Command> {
     let script_file_path = script_dir.join("script.bat");
-    io::write_string(script_block, &script_file_path)?;
+    std::fs::write(&script_file_path, script_block)?;
 
     Ok(Command::new(&script_file_path))
 }
 
-pub fn run(script: &Script) -> FailableUnit {
+pub fn log_file(context: &Context, name: &str) -> PathBuf {
+    context.working_dir.join("logs").join(format!("{}.log", name))
+}
+
+pub fn run(context: &Context, script: &Script) -> FailableUnit {
+    run_script(context, script, false)?;
+    Ok(())
+}
+
+pub fn run_captured(context: &Context, script: &Script) -> Failable<ScriptOutput> {
+    run_script(context, script, true)
+}
+
+fn run_script(context: &Context, script: &Script, capture: bool) -> Failable<ScriptOutput> {
+    let started = Instant::now();
+    let result = context.executor.run_script(script, capture);
+
+    let exit_status = match &result {
+        Ok(_) => Some(0),
+        Err(error) => error.downcast_ref::<ScriptError>().and_then(|error| error.exit_status),
+    };
+    build_report::record_script(context, script, exit_status, started.elapsed());
+
+    result
+}
+
+pub fn execute_with_retries(script: &Script, capture: bool) -> Failable<ScriptOutput> {
+    let mut attempt = 0;
+
+    loop {
+        match run_once(script, capture) {
+            Err(_) if attempt < script.retries => {
+                let delay = RETRY_BASE_DELAY * 2u32.pow(attempt);
+                attempt += 1;
+
+                logs::out(
+                    log_tag!(),
+                    &format!("Script failed, retrying in {}s (retry {} of {}) ...", delay.as_secs(), attempt, script.retries),
+                );
+                thread::sleep(delay);
+            }
+            result => return result,
+        }
+    }
+}
+
+fn run_once(script: &Script, capture: bool) -> Failable<ScriptOutput> {
+    let mut output = None;
+
     io::in_temp_dir(&mut |temp_dir| {
-        let mut command = create_command(temp_dir, &script.content)?;
+        output = Some(execute(script, capture, temp_dir)?);
+        Ok(())
+    })?;
+
+    Ok(output.ok_or("Shell script produced no result.")?)
+}
+
+fn execute(script: &Script, capture: bool, temp_dir: &Path) -> Failable<ScriptOutput> {
+    let mut command = create_command(temp_dir, &script.content)?;
+
+    command.current_dir(match &script.working_dir {
+        Some(working_dir) => working_dir.clone(),
+        _ => temp_dir.to_path_buf(),
+    });
+
+    command.envs(&script.environment);
+
+    // Output only goes through us when something needs it. Otherwise the script keeps our terminal, so tools like cargo and
+    // Gradle still show colours and progress, and nothing a launched application prints is held in memory.
+    if capture || script.quiet || script.log_file.is_some() {
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+    }
+
+    if script.timeout.is_some() {
+        isolate(&mut command);
+    }
+
+    let log: SharedLog = match &script.log_file {
+        Some(log_file) => Some(Arc::new(Mutex::new(open_log_file(log_file, script)?))),
+        None => None,
+    };
+    let tail: SharedTail = Arc::new(Mutex::new(VecDeque::with_capacity(FAILURE_OUTPUT_LINES)));
+
+    let mut child = command.spawn()?;
+    let stdout = child.stdout.take().map(|source| forward(source, false, capture, script.quiet, &log, &tail));
+    let stderr = child.stderr.take().map(|source| forward(source, true, capture, script.quiet, &log, &tail));
+
+    let status = wait(&mut child, script.timeout)?;
+
+    // Anything the script left running in the background still holds its end of the pipes, so once the script itself has
+    // finished we only wait a moment for the rest of its output rather than until those processes exit.
+    let deadline = Instant::now() + OUTPUT_GRACE_PERIOD;
+    let output = ScriptOutput {
+        stdout: stdout.map(|stdout| stdout.collect(deadline)).unwrap_or_default(),
+        stderr: stderr.map(|stderr| stderr.collect(deadline)).unwrap_or_default(),
+    };
+
+    match (status, script.timeout) {
+        (Some(status), _) if status.success() => Ok(output),
+        (Some(status), _) => {
+            Err(failure(script, &format!("returned failed status code: {:?}", status.code()), status.code(), &tail))
+        }
+        (None, Some(timeout)) => Err(failure(script, &format!("timed out after {} seconds", timeout.as_secs()), None, &tail)),
+        (None, None) => Err(failure(script, "was stopped", None, &tail)),
+    }
+}
+
+fn open_log_file(log_file: &PathBuf, script: &Script) -> Failable<std::fs::File> {
+    std::fs::create_dir_all(log_file.parent().ok_or("Parent directory not found")?)?;
+
+    // Logs are appended to so every attempt (and every build) of a step can be compared, each one starts with the script it ran.
+    let mut file = OpenOptions::new().create(true).append(true).open(log_file)?;
+    writeln!(file, "\n==> {}", script.content.replace('\n', "\n==> "))?;
+
+    Ok(file)
+}
+
+// Output being read from one of the script's pipes on a thread of its own.
+struct Forwarded {
+    captured: Arc<Mutex<String>>,
+    finished: Receiver<()>,
+}
+
+impl Forwarded {
+    // Everything read by the deadline, the reading thread is left behind if the pipe is still open by then.
+    fn collect(self, deadline: Instant) -> String {
+        let _ = self.finished.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        self.captured.lock().map(|captured| captured.clone()).unwrap_or_default()
+    }
+}
+
+fn forward<R: Read + Send + 'static>(
+    source: R,
+    is_stderr: bool,
+    capture: bool,
+    quiet: bool,
+    log: &SharedLog,
+    tail: &SharedTail,
+) -> Forwarded {
+    let log = log.clone();
+    let tail = tail.clone();
+    let captured = Arc::new(Mutex::new(String::new()));
+    let (finished_sender, finished) = mpsc::channel();
+
+    let shared_captured = captured.clone();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(source);
+        let mut line = Vec::new();
+
+        // Lines are read as bytes because tools don't always print valid UTF-8, and the original bytes are echoed unchanged.
+        while let Ok(count) = reader.read_until(b'\n', &mut line) {
+            if count == 0 {
+                break;
+            }
+
+            // Quiet output is kept out of the terminal, but still ends up in the log and in the error should the script fail.
+            if !quiet && is_stderr {
+                let _ = std::io::stderr().write_all(&line);
+            } else if !quiet {
+                let _ = std::io::stdout().write_all(&line);
+            }
+
+            if let Some(log) = &log {
+                if let Ok(mut log) = log.lock() {
+                    let _ = log.write_all(&line);
+                }
+            }
+
+            let text = String::from_utf8_lossy(&line);
+            if capture {
+                if let Ok(mut captured) = shared_captured.lock() {
+                    captured.push_str(&text);
+                }
+            }
+
+            if let Ok(mut tail) = tail.lock() {
+                if tail.len() == FAILURE_OUTPUT_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(text.trim_end().to_owned());
+            }
 
-        command.current_dir(match &script.working_dir {
-            Some(working_dir) => working_dir.clone(),
-            _ => temp_dir.to_path_buf(),
+            line.clear();
+        }
+
+        // Nobody is listening any more if the script finished long before whatever kept this output open.
+        let _ = finished_sender.send(());
+    });
+
+    Forwarded { captured, finished }
+}
+
+fn wait(child: &mut Child, timeout: Option<Duration>) -> Failable<Option<ExitStatus>> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return Ok(Some(child.wait()?)),
+    };
+
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+
+        if Instant::now() >= deadline {
+            kill(child);
+            child.wait()?;
+            return Ok(None);
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// The script is only a wrapper around the real work (cargo, make etc), so on timeout everything it started has to go too.
+// Scripts with a timeout run in a process group of their own, which is then killed as a whole. Only those scripts, as a
+// process group outside of the terminal's foreground group no longer receives Ctrl+C.
+#[cfg(not(target_os = "windows"))]
+fn isolate(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    extern "C" {
+        fn setpgid(pid: i32, pgid: i32) -> i32;
+    }
+
+    // Only async signal safe calls are allowed between fork and exec, which 'setpgid' is.
+    unsafe {
+        command.pre_exec(|| match setpgid(0, 0) {
+            0 => Ok(()),
+            _ => Err(std::io::Error::last_os_error()),
         });
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn kill(child: &mut Child) {
+    let _ = Command::new("kill").args(["-KILL", "--", &format!("-{}", child.id())]).status();
+    let _ = child.kill();
+}
+
+// Windows has no process groups to speak of, 'taskkill /T' walks the tree of processes the script started instead.
+#[cfg(target_os = "windows")]
+fn isolate(_: &mut Command) {}
 
-        command.envs(&script.environment);
-        command.stdout(Stdio::inherit());
-        command.stderr(Stdio::inherit());
+#[cfg(target_os = "windows")]
+fn kill(child: &mut Child) {
+    let _ = Command::new("taskkill").args(["/T", "/F", "/PID", &child.id().to_string()]).status();
+    let _ = child.kill();
+}
 
-        let output = command.output()?;
-        let status_code = output.status.code().ok_or("Failed to get shell script status code.")?;
+fn failure(script: &Script, reason: &str, exit_status: Option<i32>, tail: &SharedTail) -> Box<dyn std::error::Error> {
+    let mut message = format!("Shell script {}.\n", reason);
 
-        if output.status.success() {
-            Ok(())
-        } else {
-            Err(format!("Shell script returned failed status code: {:?}", &status_code).into())
+    match &script.working_dir {
+        Some(working_dir) => message += &format!("Working dir: {:?}\n", working_dir),
+        None => message += "Working dir: (temporary directory)\n",
+    }
+
+    message += "Script:\n";
+    for line in script.content.lines() {
+        message += &format!("    > {}\n", line);
+    }
+
+    // Scripts writing straight to the terminal leave no tail, their output is already right above the error.
+    if let Some(tail) = tail.lock().ok().filter(|tail| !tail.is_empty()) {
+        message += &format!("Last {} lines of output:\n", tail.len());
+        for line in tail.iter() {
+            message += &format!("    | {}\n", line);
         }
+    }
+
+    if let Some(log_file) = &script.log_file {
+        message += &format!("Full output: {:?}\n", log_file);
+    }
+
+    Box::new(ScriptError {
+        message: message.trim_end().to_owned(),
+        exit_status,
     })
 }
+
+#[cfg(test)]
+#[cfg(not(target_os = "windows"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_stops_everything_the_script_started() {
+        let started = Instant::now();
+        let result = execute_with_retries(&Script::new("sleep 30 &\nsleep 30").timeout(Duration::from_secs(1)), true);
+
+        assert!(result.unwrap_err().to_string().contains("timed out after 1 seconds"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn background_processes_do_not_hold_up_the_output() {
+        let started = Instant::now();
+        let output = execute_with_retries(&Script::new("sleep 30 &\necho done"), true).unwrap();
+
+        assert_eq!(output.stdout.trim(), "done");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{
+    core::{context::Context, executor, failable_unit::FailableUnit, io, logs, script::Script, scripts},
+    log_tag,
+};
+use std::path::PathBuf;
+
+const VALIDATOR: &str = "glslangValidator";
+
+// The application prepends one of these before compiling a shader (see 'crust-main/src/opengl/shader_program.rs'), so
+// every shader is checked both ways: as GLSL ES 100 for the mobile and web targets and as GLSL 120 for the desktop ones.
+struct Dialect {
+    name: &'static str,
+    vertex_prefix: &'static str,
+    fragment_prefix: &'static str,
+}
+
+const DIALECTS: &[Dialect] = &[
+    Dialect {
+        name: "es100",
+        vertex_prefix: "#version 100\n",
+        fragment_prefix: "#version 100\nprecision mediump float;\n",
+    },
+    Dialect {
+        name: "120",
+        vertex_prefix: "#version 120\n",
+        fragment_prefix: "#version 120\n",
+    },
+];
+
+#[allow(clippy::ptr_arg)]
+pub fn is_shader(path: &PathBuf) -> bool {
+    matches!(path.extension().and_then(|extension| extension.to_str()), Some("vert") | Some("frag"))
+}
+
+// Validation relies on an external tool, so a machine without it still builds and the shaders are only checked when the
+// application compiles them.
+pub fn should_validate(context: &Context) -> bool {
+    if !context.config.assets.validate_shaders {
+        return false;
+    }
+
+    if executor::probe_command(context, VALIDATOR, &["--version"]).is_some() {
+        return true;
+    }
+
+    logs::out(
+        log_tag!(),
+        &format!(
+            "Warning: '{}' not found on PATH, shaders are not validated. Install the Khronos glslang tools to check them.",
+            VALIDATOR
+        ),
+    );
+
+    false
+}
+
+pub fn validate(context: &Context, path: &PathBuf, content: &str) -> FailableUnit {
+    let file_name = path.file_name().ok_or("Missing file name")?.to_string_lossy().to_string();
+    let is_vertex = path.extension().map_or(false, |extension| extension == "vert");
+
+    io::in_temp_dir(&mut |temp_dir| {
+        for dialect in DIALECTS {
+            let prefix = if is_vertex {
+                dialect.vertex_prefix
+            } else {
+                dialect.fragment_prefix
+            };
+
+            // The stage is taken from the extension, so the dialect goes in front of it rather than after it.
+            let variant_path = temp_dir.join(format!("{}.{}", dialect.name, file_name));
+            io::write_string(context, &format!("{}{}", prefix, content), &variant_path)?;
+
+            scripts::run(context, &Script::new(&format!("{} {:?}", VALIDATOR, &variant_path))).map_err(|error| {
+                format!(
+                    "Shader {:?} is not valid GLSL {} (line numbers include the {} line prefix):\n{}",
+                    path,
+                    dialect.name,
+                    prefix.lines().count(),
+                    error
+                )
+            })?;
+        }
+
+        Ok(())
+    })
+}
@@ -3,6 +3,7 @@ use crate::core::failable::Failable;
 const ANDROID: &str = "android";
 const EMSCRIPTEN: &str = "emscripten";
 const IOS: &str = "ios";
+const LINUX: &str = "linux";
 const MACOS_CONSOLE: &str = "macos-console";
 const MACOS_DESKTOP: &str = "macos-desktop";
 const WINDOWS: &str = "windows";
@@ -12,6 +13,7 @@ pub enum Target {
     Android,
     Emscripten,
     Ios,
+    Linux,
     MacOSConsole,
     MacOSDesktop,
     Windows,
@@ -23,6 +25,7 @@ impl Target {
             ANDROID => Ok(Target::Android),
             EMSCRIPTEN => Ok(Target::Emscripten),
             IOS => Ok(Target::Ios),
+            LINUX => Ok(Target::Linux),
             MACOS_CONSOLE => Ok(Target::MacOSConsole),
             MACOS_DESKTOP => Ok(Target::MacOSDesktop),
             WINDOWS => Ok(Target::Windows),
@@ -30,11 +33,12 @@ impl Target {
         }
     }
 
-    pub fn id(&self) -> &str {
+    pub fn id(&self) -> &'static str {
         match self {
             Target::Android => ANDROID,
             Target::Emscripten => EMSCRIPTEN,
             Target::Ios => IOS,
+            Target::Linux => LINUX,
             Target::MacOSConsole => MACOS_CONSOLE,
             Target::MacOSDesktop => MACOS_DESKTOP,
             Target::Windows => WINDOWS,
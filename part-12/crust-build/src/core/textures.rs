@@ -0,0 +1,75 @@
+use crate::core::{config::TextureFormat, failable::Failable};
+use std::path::PathBuf;
+
+// The texture format loaded by 'crust-main/src/core/io.rs', every number is little endian:
+// "CTEX", u32 version, u32 pixel format (0 rgba8, 1 rgb565, 2 rgba4444), u32 width, u32 height,
+// then the rows of pixels from top to bottom, 4 bytes per pixel for rgba8 and a u16 per pixel otherwise.
+const MAGIC: &[u8; 4] = b"CTEX";
+const VERSION: u32 = 1;
+
+#[allow(clippy::ptr_arg)]
+pub fn is_texture_source(path: &PathBuf) -> bool {
+    path.extension().map_or(false, |extension| extension == "png")
+}
+
+pub fn convert_png(path: &PathBuf, content: &[u8], format: TextureFormat) -> Failable<Vec<u8>> {
+    let rgba = decode_png(content).map_err(|error| format!("Failed to decode {:?}: {}", path, error))?;
+
+    let format_code: u32 = match format {
+        TextureFormat::Rgba8 => 0,
+        TextureFormat::Rgb565 => 1,
+        TextureFormat::Rgba4444 => 2,
+    };
+
+    let mut data = Vec::with_capacity(20 + rgba.pixels.len());
+
+    data.extend_from_slice(MAGIC);
+    for value in &[VERSION, format_code, rgba.width, rgba.height] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    for pixel in rgba.pixels.chunks_exact(4) {
+        let (r, g, b, a) = (pixel[0] as u16, pixel[1] as u16, pixel[2] as u16, pixel[3] as u16);
+
+        match format {
+            TextureFormat::Rgba8 => data.extend_from_slice(pixel),
+            TextureFormat::Rgb565 => data.extend_from_slice(&((r >> 3) << 11 | (g >> 2) << 5 | b >> 3).to_le_bytes()),
+            TextureFormat::Rgba4444 => {
+                data.extend_from_slice(&((r >> 4) << 12 | (g >> 4) << 8 | (b >> 4) << 4 | a >> 4).to_le_bytes())
+            }
+        }
+    }
+
+    Ok(data)
+}
+
+struct RgbaImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+// Whatever the PNG holds (palette, grey, 16 bit channels) is widened or narrowed to 8 bit RGBA first.
+fn decode_png(content: &[u8]) -> Failable<RgbaImage> {
+    let mut decoder = png::Decoder::new(content);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+
+    let (info, mut reader) = decoder.read_info()?;
+    let mut buffer = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buffer)?;
+
+    let (color_type, _) = reader.output_color_type();
+    let pixels = match color_type {
+        png::ColorType::RGBA => buffer,
+        png::ColorType::RGB => buffer.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|p| [*p, *p, *p, 255]).collect(),
+        png::ColorType::Indexed => return Err("Palette image was not expanded".into()),
+    };
+
+    Ok(RgbaImage {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
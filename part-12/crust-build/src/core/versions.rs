@@ -0,0 +1,110 @@
+use crate::{
+    core::{
+        context::Context, failable::Failable, failable_unit::FailableUnit, io, logs, manifests, script::Script, scripts,
+        target::Target, variant::Variant,
+    },
+    log_tag,
+};
+
+// These are passed to every cargo invocation so crust-main can compile them in with 'option_env!'.
+pub const VERSION_ENV: &str = "CRUST_VERSION";
+pub const BUILD_NUMBER_ENV: &str = "CRUST_BUILD_NUMBER";
+pub const GIT_DESCRIBE_ENV: &str = "CRUST_GIT_DESCRIBE";
+
+const VERSION_FILE_NAME: &str = "version.properties";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub version: String,
+    pub build_number: u32,
+    pub git_describe: String,
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{} (build {}, {})", self.version, self.build_number, self.git_describe)
+    }
+}
+
+pub fn cargo_version(context: &Context) -> Failable<String> {
+    let manifest_path = context.source_dir.join("Cargo.toml");
+    Ok(manifests::package_field(&context.source_dir, "version")?
+        .as_str()
+        .ok_or(format!("Field 'package/version' not found in {:?}", &manifest_path))?
+        .to_owned())
+}
+
+pub fn resolve(context: &Context) -> Failable<Version> {
+    Ok(Version {
+        version: cargo_version(context)?,
+        build_number: build_number(context)?,
+        git_describe: git(context, &["describe", "--tags", "--always", "--dirty"]).unwrap_or_else(|| "unknown".to_owned()),
+    })
+}
+
+pub fn stamp(context: &Context) -> Failable<Version> {
+    let version = resolve(context)?;
+    logs::out(log_tag!(), &format!("Version: {}", &version));
+
+    // Child processes inherit our environment, so setting these once reaches every cargo invocation no matter which target runs it.
+    std::env::set_var(VERSION_ENV, &version.version);
+    std::env::set_var(BUILD_NUMBER_ENV, version.build_number.to_string());
+    std::env::set_var(GIT_DESCRIBE_ENV, &version.git_describe);
+
+    // Nothing tracked is ever changed. Gradle and Xcode read the version back from this file once the Rust build is done,
+    // no matter whether crust-build or the IDE started the build.
+    if matches!(context.target, Target::Android | Target::Ios | Target::MacOSDesktop) {
+        write_version_file(context, &version)?;
+    }
+
+    Ok(version)
+}
+
+fn build_number(context: &Context) -> Failable<u32> {
+    // CI systems usually have their own build counter, which always wins over the commit count.
+    if let Ok(build_number) = std::env::var(BUILD_NUMBER_ENV) {
+        return Ok(build_number
+            .trim()
+            .parse()
+            .map_err(|_| format!("{} must be a positive number, but was {:?}", BUILD_NUMBER_ENV, &build_number))?);
+    }
+
+    // App stores need a build number that goes up with every upload. Nothing in the checkout can promise that: the commit
+    // count goes backwards after a rebase, differs between branches and stays the same when a commit is built twice.
+    if context.variant == Variant::Release {
+        return Err(
+            format!("Release builds need a build number, set {} to your CI system's build counter.", BUILD_NUMBER_ENV).into()
+        );
+    }
+
+    // Debug builds are never uploaded, so the commit count is only a rough hint of how recent a build is.
+    Ok(git(context, &["rev-list", "--count", "HEAD"]).and_then(|count| count.parse().ok()).unwrap_or(1))
+}
+
+// Outside of a git checkout (for example a source archive) there is simply nothing to ask, so failures are not errors.
+fn git(context: &Context, args: &[&str]) -> Option<String> {
+    let script = Script::new(&format!("git {}", args.join(" "))).working_dir(&context.source_dir).quiet();
+    let output = scripts::run_captured(context, &script).ok()?.stdout.trim().to_owned();
+
+    // A dry run doesn't run anything and so has no answer either.
+    if output.is_empty() {
+        None
+    } else {
+        Some(output)
+    }
+}
+
+// Read by 'app/build.gradle' for Android and by the 'Stamp Version' build phase of the Xcode projects, both fall back to
+// version 1.0 (1) for a fresh checkout that was never built.
+fn write_version_file(context: &Context, version: &Version) -> FailableUnit {
+    let path = context.working_dir.join(VERSION_FILE_NAME);
+    let content = format!("versionName={}\nversionCode={}\n", version.version, version.build_number);
+
+    // Rewriting an unchanged file would needlessly make the IDE consider everything that depends on it out of date.
+    if path.exists() && io::read_string(&path)? == content {
+        return Ok(());
+    }
+
+    logs::out(log_tag!(), &format!("Writing version to: {:?}", &path));
+    io::write_string(context, &content, &path)
+}
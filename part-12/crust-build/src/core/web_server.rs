@@ -0,0 +1,222 @@
+use crate::{
+    core::{failable::Failable, failable_unit::FailableUnit, logs},
+    log_tag,
+};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+};
+
+#[allow(clippy::ptr_arg)]
+pub fn start(root_dir: &PathBuf, bind_address: &str, port: u16) -> Failable<SocketAddr> {
+    // Binding on the calling thread means a port that is already taken is reported as an error rather than lost in a background thread.
+    let listener = TcpListener::bind((bind_address, port))
+        .map_err(|error| format!("Unable to serve on {}:{}: {}", bind_address, port, error))?;
+    let address = listener.local_addr()?;
+    let root_dir = root_dir.clone();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let root_dir = root_dir.clone();
+
+            std::thread::spawn(move || {
+                if let Err(error) = handle(stream, &root_dir) {
+                    logs::out(log_tag!(), &format!("Request failed: {:?}", error));
+                }
+            });
+        }
+    });
+
+    Ok(address)
+}
+
+fn handle(mut stream: TcpStream, root_dir: &Path) -> FailableUnit {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // We don't need any of the request headers, but they have to be consumed before the response is written.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+
+    if method != "GET" && method != "HEAD" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"Method not allowed", method == "HEAD");
+    }
+
+    let file_path = match resolve_path(root_dir, target) {
+        Some(file_path) => file_path,
+        None => return respond(&mut stream, "400 Bad Request", "text/plain", b"Bad request", method == "HEAD"),
+    };
+
+    match std::fs::read(&file_path) {
+        Ok(content) => respond(&mut stream, "200 OK", content_type(&file_path), &content, method == "HEAD"),
+        Err(_) => respond(&mut stream, "404 Not Found", "text/plain", b"Not found", method == "HEAD"),
+    }
+}
+
+fn resolve_path(root_dir: &Path, target: &str) -> Option<PathBuf> {
+    let path = percent_decode(target.split(['?', '#']).next()?)?;
+    let relative_path = Path::new(path.trim_start_matches('/'));
+
+    // Only plain path segments are allowed so a request can never escape the directory being served.
+    if !relative_path.components().all(|component| matches!(component, Component::Normal(_))) {
+        return None;
+    }
+
+    let file_path = root_dir.join(relative_path);
+
+    if file_path.is_dir() {
+        Some(file_path.join("index.html"))
+    } else {
+        Some(file_path)
+    }
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(index + 1..index + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    // Browsers refuse to stream compile WebAssembly unless it is served as 'application/wasm'.
+    match path.extension().and_then(|extension| extension.to_str()).unwrap_or_default() {
+        "html" => "text/html; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "wasm" => "application/wasm",
+        "css" => "text/css; charset=utf-8",
+        "json" => "application/json",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8], head_only: bool) -> FailableUnit {
+    // Caching is disabled so a browser refresh always picks up the latest build.
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+
+    if !head_only {
+        stream.write_all(body)?;
+    }
+
+    Ok(stream.flush()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn serve() -> (tempfile::TempDir, SocketAddr) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root_dir = temp_dir.path().join("served");
+        std::fs::create_dir_all(root_dir.join("sub dir")).unwrap();
+        std::fs::write(root_dir.join("index.html"), "<html></html>").unwrap();
+        std::fs::write(root_dir.join("crust.wasm"), [0u8, 97, 115, 109]).unwrap();
+        std::fs::write(root_dir.join("sub dir").join("index.html"), "nested").unwrap();
+
+        // Something next to the served directory that a traversal would be after.
+        std::fs::write(temp_dir.path().join("secret.txt"), "secret").unwrap();
+
+        let address = start(&root_dir, "127.0.0.1", 0).unwrap();
+        (temp_dir, address)
+    }
+
+    fn request(address: SocketAddr, method: &str, target: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\n\r\n", method, target).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.to_owned(), body.to_owned())
+    }
+
+    #[test]
+    fn serves_files_and_directory_indexes() {
+        let (_temp_dir, address) = serve();
+
+        let (head, body) = request(address, "GET", "/");
+        assert!(head.starts_with("HTTP/1.1 200 OK"));
+        assert!(head.contains("Content-Type: text/html; charset=utf-8"));
+        assert_eq!(body, "<html></html>");
+
+        let (head, body) = request(address, "GET", "/sub%20dir/?cache=bust");
+        assert!(head.starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(body, "nested");
+
+        let (head, _) = request(address, "GET", "/missing.js");
+        assert!(head.starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[test]
+    fn head_sends_headers_without_a_body() {
+        let (_temp_dir, address) = serve();
+
+        let (head, body) = request(address, "HEAD", "/crust.wasm");
+        assert!(head.starts_with("HTTP/1.1 200 OK"));
+        assert!(head.contains("Content-Type: application/wasm"));
+        assert!(head.contains("Content-Length: 4"));
+        assert_eq!(body, "");
+
+        let (head, _) = request(address, "POST", "/crust.wasm");
+        assert!(head.starts_with("HTTP/1.1 405 Method Not Allowed"));
+    }
+
+    #[test]
+    fn refuses_to_leave_the_served_directory() {
+        let (_temp_dir, address) = serve();
+
+        for target in &[
+            "/../secret.txt",
+            "/%2e%2e/secret.txt",
+            "/sub%20dir/..%2f..%2fsecret.txt",
+            "/sub%20dir/../../secret.txt",
+        ] {
+            let (head, body) = request(address, "GET", target);
+            assert!(head.starts_with("HTTP/1.1 400 Bad Request"), "{} => {}", target, head);
+            assert_ne!(body, "secret");
+        }
+    }
+
+    #[test]
+    fn content_types() {
+        assert_eq!(content_type(Path::new("crust.wasm")), "application/wasm");
+        assert_eq!(content_type(Path::new("crust.js")), "text/javascript; charset=utf-8");
+        assert_eq!(content_type(Path::new("index.html")), "text/html; charset=utf-8");
+        assert_eq!(content_type(Path::new("crust.data")), "application/octet-stream");
+        assert_eq!(content_type(Path::new("no-extension")), "application/octet-stream");
+    }
+}
@@ -0,0 +1,338 @@
+use crate::{
+    android, android_ndk,
+    core::{context::Context, executor, failable::Failable, failable_unit::FailableUnit, target::Target},
+    emscripten, ios, windows,
+};
+use std::path::PathBuf;
+
+// Building SDL from source and the Rust build directories for several architectures can comfortably use a few gigabytes.
+const MIN_FREE_DISK_SPACE_MB: u64 = 5 * 1024;
+
+#[derive(PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    name: String,
+    status: Status,
+    detail: String,
+    hint: String,
+}
+
+impl Check {
+    fn pass(name: &str, detail: &str) -> Self {
+        Check {
+            name: name.to_owned(),
+            status: Status::Pass,
+            detail: detail.to_owned(),
+            hint: String::new(),
+        }
+    }
+
+    fn warn(name: &str, detail: &str, hint: &str) -> Self {
+        Check {
+            name: name.to_owned(),
+            status: Status::Warn,
+            detail: detail.to_owned(),
+            hint: hint.to_owned(),
+        }
+    }
+
+    fn fail(name: &str, detail: &str, hint: &str) -> Self {
+        Check {
+            name: name.to_owned(),
+            status: Status::Fail,
+            detail: detail.to_owned(),
+            hint: hint.to_owned(),
+        }
+    }
+}
+
+pub fn check(context: &Context) -> FailableUnit {
+    context.print_summary();
+
+    let mut checks = Vec::new();
+
+    for name in required_executables(context) {
+        checks.push(check_executable(name));
+    }
+
+    checks.extend(check_environment(context));
+
+    // The Xcode based targets bundle the source assets themselves, everything else validates its shaders while processing them.
+    if context.config.assets.validate_shaders && !matches!(context.target, Target::Ios | Target::MacOSDesktop) {
+        checks.push(check_glslang());
+    }
+
+    checks.extend(check_rust_targets(context)?);
+    checks.push(check_disk_space(context));
+
+    print(&checks);
+
+    let failures = checks.iter().filter(|check| check.status == Status::Fail).count();
+
+    if failures > 0 {
+        return Err(format!("{} of {} checks failed for target '{}'.", failures, checks.len(), context.target.id()).into());
+    }
+
+    Ok(())
+}
+
+fn required_executables(context: &Context) -> Vec<&'static str> {
+    let mut executables = vec!["cargo", "rustup"];
+
+    executables.extend(match context.target {
+        Target::Android => vec!["adb"],
+        Target::Emscripten => vec!["python3"],
+        Target::Ios | Target::MacOSDesktop => vec!["xcodegen", "xcodebuild", "lipo"],
+        Target::Linux => vec!["pkg-config", "make"],
+        Target::MacOSConsole => vec!["xcodegen", "xcodebuild", "lipo", "install_name_tool"],
+        Target::Windows if windows::uses_gnu_toolchain() => vec!["x86_64-w64-mingw32-gcc"],
+        Target::Windows => vec![],
+    });
+
+    executables
+}
+
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let file_name = if cfg!(target_os = "windows") {
+        format!("{}.exe", name)
+    } else {
+        name.to_owned()
+    };
+
+    std::env::split_paths(&std::env::var_os("PATH")?).map(|dir| dir.join(&file_name)).find(|path| path.is_file())
+}
+
+fn check_executable(name: &str) -> Check {
+    let check_name = format!("executable: {}", name);
+
+    match find_executable(name) {
+        Some(path) => Check::pass(&check_name, &path.display().to_string()),
+        None => Check::fail(&check_name, "not found on PATH", &executable_hint(name)),
+    }
+}
+
+fn executable_hint(name: &str) -> String {
+    match name {
+        "cargo" | "rustup" => "Install Rust via https://rustup.rs".to_owned(),
+        "adb" => "Install the Android SDK platform tools and add 'platform-tools' to PATH.".to_owned(),
+        "xcodegen" => "Install via 'brew install xcodegen'.".to_owned(),
+        "xcodebuild" | "lipo" | "install_name_tool" => {
+            "Install Xcode and its command line tools via 'xcode-select --install'.".to_owned()
+        }
+        "pkg-config" | "make" => {
+            "Install via your package manager, for example 'apt install build-essential pkg-config'.".to_owned()
+        }
+        "x86_64-w64-mingw32-gcc" => {
+            "Install the MinGW-w64 cross compiler, for example 'apt install gcc-mingw-w64-x86-64'.".to_owned()
+        }
+        _ => format!("Install '{}' and make sure it is on PATH.", name),
+    }
+}
+
+fn check_environment(context: &Context) -> Vec<Check> {
+    match context.target {
+        Target::Android => check_android_ndk(context),
+        Target::Emscripten => vec![check_emscripten_sdk(context)],
+        Target::Linux => vec![check_patchelf()],
+        Target::Windows if windows::uses_gnu_toolchain() => vec![check_wine()],
+        _ => vec![],
+    }
+}
+
+fn check_patchelf() -> Check {
+    // Only the 'package' command needs it, and only when linking against the system SDL, so a missing patchelf is just a warning.
+    match find_executable("patchelf") {
+        Some(path) => Check::pass("executable: patchelf", &path.display().to_string()),
+        None => Check::warn(
+            "executable: patchelf",
+            "not found on PATH",
+            "Needed to package builds that use the system SDL, install via 'apt install patchelf'.",
+        ),
+    }
+}
+
+fn check_glslang() -> Check {
+    // Without it shaders are packed unchecked and a build only warns about it, so it doesn't fail the doctor either.
+    match find_executable("glslangValidator") {
+        Some(path) => Check::pass("executable: glslangValidator", &path.display().to_string()),
+        None => Check::warn(
+            "executable: glslangValidator",
+            "not found on PATH",
+            "Needed to validate shaders, install the Khronos glslang tools, for example 'apt install glslang-tools' or 'brew install glslang'.",
+        ),
+    }
+}
+
+fn check_wine() -> Check {
+    // Wine is only used by the 'run' command to smoke test cross compiled builds, building works fine without it.
+    match find_executable("wine") {
+        Some(path) => Check::pass("executable: wine", &path.display().to_string()),
+        None => Check::warn(
+            "executable: wine",
+            "not found on PATH",
+            "Needed to run cross compiled Windows builds, install via 'apt install wine64'.",
+        ),
+    }
+}
+
+fn check_android_ndk(context: &Context) -> Vec<Check> {
+    let ndk_dir = match android_ndk::ndk_dir() {
+        Ok(ndk_dir) => ndk_dir,
+        Err(error) => {
+            return vec![Check::fail(
+                "env: ANDROID_NDK_ROOT",
+                &error.to_string(),
+                "Install the NDK via the Android SDK manager and set ANDROID_NDK_ROOT to its directory.",
+            )]
+        }
+    };
+
+    let mut checks = vec![Check::pass("env: ANDROID_NDK_ROOT", &ndk_dir.display().to_string())];
+
+    let toolchain = match android_ndk::resolve(&ndk_dir, context.config.android.ndk_api_level) {
+        Ok(toolchain) => toolchain,
+        Err(error) => {
+            checks.push(Check::fail(
+                "ndk: toolchain",
+                &error.to_string(),
+                "Install a supported NDK (r19 or newer) built for this host operating system.",
+            ));
+            return checks;
+        }
+    };
+
+    checks.push(Check::pass("ndk: version", &toolchain.version.to_string()));
+    checks.push(Check::pass("ndk: host prebuilt", &toolchain.bin_dir.display().to_string()));
+
+    checks.push(match android::check_toolchain(context, &toolchain) {
+        Ok(_) => Check::pass(
+            "ndk: toolchain",
+            &format!(
+                "API level {}, {} binutils",
+                toolchain.api_level.map_or("NDK minimum".to_owned(), |api_level| api_level.to_string()),
+                if toolchain.uses_llvm_binutils() { "LLVM" } else { "GNU" }
+            ),
+        ),
+        Err(error) => Check::fail(
+            "ndk: toolchain",
+            &error.to_string(),
+            "Change 'android.ndk_api_level' or 'android.architectures' in crust-build.toml, or install a different NDK.",
+        ),
+    });
+
+    checks
+}
+
+fn check_emscripten_sdk(context: &Context) -> Check {
+    let sdk_dir = emscripten::sdk_dir(context);
+
+    // 'emsdk activate' writes a '.emscripten' config file into the SDK directory.
+    if sdk_dir.join(".emscripten").exists() {
+        Check::pass("emsdk: activated", &sdk_dir.display().to_string())
+    } else {
+        Check::warn(
+            "emsdk: activated",
+            &format!("{:?} not installed yet", &sdk_dir),
+            "The Emscripten SDK is downloaded and activated on the first build.",
+        )
+    }
+}
+
+fn required_rust_targets(context: &Context) -> Failable<Vec<String>> {
+    Ok(match context.target {
+        Target::Android => android::rust_triples(context)?,
+        Target::Emscripten => vec!["wasm32-unknown-emscripten".to_owned()],
+        Target::Ios => ios::architectures(context),
+        Target::MacOSDesktop => context.config.macos.desktop_architectures.clone(),
+        Target::Windows if windows::uses_gnu_toolchain() => vec![windows::GNU_RUST_TRIPLE.to_owned()],
+        Target::Linux | Target::MacOSConsole | Target::Windows => vec![],
+    })
+}
+
+fn check_rust_targets(context: &Context) -> Failable<Vec<Check>> {
+    let required = required_rust_targets(context)?;
+
+    if required.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let installed = executor::probe_command(context, "rustup", &["target", "list", "--installed"])
+        .map(|output| output.lines().map(|line| line.trim().to_owned()).collect::<Vec<String>>())
+        .unwrap_or_default();
+
+    Ok(required
+        .iter()
+        .map(|triple| {
+            let name = format!("rustup target: {}", triple);
+
+            if installed.contains(triple) {
+                Check::pass(&name, "installed")
+            } else {
+                Check::warn(
+                    &name,
+                    "not installed",
+                    &format!("Installed on the next build, or run 'rustup target add {}'.", triple),
+                )
+            }
+        })
+        .collect())
+}
+
+fn check_disk_space(context: &Context) -> Check {
+    let name = "disk: free space";
+
+    match free_disk_space_mb(context, &context.target_home_dir) {
+        Some(free) if free >= MIN_FREE_DISK_SPACE_MB => Check::pass(name, &format!("{} MB", free)),
+        Some(free) => Check::fail(
+            name,
+            &format!("{} MB (need {} MB)", free, MIN_FREE_DISK_SPACE_MB),
+            &format!("Free up space on the drive containing {:?}.", &context.target_home_dir),
+        ),
+        None => Check::warn(name, "unknown", "Could not determine free disk space."),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn free_disk_space_mb(_: &Context, _: &PathBuf) -> Option<u64> {
+    None
+}
+
+#[cfg(not(target_os = "windows"))]
+#[allow(clippy::ptr_arg)]
+fn free_disk_space_mb(context: &Context, dir: &PathBuf) -> Option<u64> {
+    // The directory might not exist before the first build, so we measure the closest parent that does.
+    let existing_dir = dir.ancestors().find(|ancestor| ancestor.exists())?;
+    let output = executor::probe_command(context, "df", &["-Pk", &existing_dir.to_string_lossy()])?;
+
+    // POSIX format: a header line, then 'filesystem blocks used available capacity mount' in 1024 byte blocks.
+    let available_kb: u64 = output.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+
+    Some(available_kb / 1024)
+}
+
+fn print(checks: &[Check]) {
+    let name_width = checks.iter().map(|check| check.name.len()).max().unwrap_or(0);
+
+    println!("---------------------------------------------");
+
+    for check in checks {
+        let status = match check.status {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        };
+
+        println!("{}  {:width$}  {}", status, check.name, check.detail, width = name_width);
+
+        if !check.hint.is_empty() {
+            println!("      {:width$}  -> {}", "", check.hint, width = name_width);
+        }
+    }
+
+    println!("---------------------------------------------");
+}
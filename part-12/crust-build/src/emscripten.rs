@@ -1,54 +1,195 @@
 use crate::{
-    core::{context::Context, failable_unit::FailableUnit, io, logs, outputs, remote_zips, script::Script, scripts},
+    core::{
+        assets,
+        build_target::{self, BuildTarget},
+        context::Context,
+        failable::Failable,
+        failable_unit::FailableUnit,
+        fingerprint::Fingerprint,
+        fingerprints, io, logs, outputs, remote_zips,
+        script::Script,
+        scripts,
+        target::Target,
+        web_server,
+    },
     log_tag,
 };
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
-const EMSCRIPTEN_VERSION: &str = "2.0.32";
-const EMSCRIPTEN_URL: &str = "https://github.com/emscripten-core/emsdk/archive/refs/tags/2.0.32.zip";
+pub struct EmscriptenTarget;
 
-pub fn build(context: &Context) -> FailableUnit {
-    context.print_summary();
-    install_rust_dependencies()?;
-    configure_emscripten_sdk(context)?;
-    compile_application(context)?;
-    create_output(context)?;
-    open_in_browser(context)?;
+impl BuildTarget for EmscriptenTarget {
+    fn target(&self) -> Target {
+        Target::Emscripten
+    }
+
+    fn prepare_dependencies(&self, context: &Context) -> FailableUnit {
+        install_rust_dependencies(context)?;
+        configure_emscripten_sdk(context)
+    }
+
+    fn compile(&self, context: &Context) -> FailableUnit {
+        compile_application(context)
+    }
+
+    fn output_files(&self, context: &Context) -> Failable<Vec<PathBuf>> {
+        let build_variant_dir = build_variant_dir(context);
+        let binary_name = &context.binary_name;
+
+        Ok(vec![
+            build_variant_dir.join(format!("{}.wasm", binary_name)),
+            build_variant_dir.join(format!("{}.data", binary_name)),
+            build_variant_dir.join(format!("{}.js", binary_name)),
+        ])
+    }
+
+    fn output_fingerprint(&self, context: &Context, fingerprint: Fingerprint) -> Failable<Fingerprint> {
+        // The page is rendered up front so only the settings that end up in it count, rather than say the port it is served on.
+        Ok(fingerprint.value(&render_index_html(context, &html_template_file(context))?))
+    }
+
+    fn complete_output(&self, context: &Context) -> FailableUnit {
+        let index_html = render_index_html(context, &html_template_file(context))?;
+        io::write_string(context, &index_html, &outputs::output_dir(context).join("index.html"))
+    }
+
+    fn run(&self, context: &Context) -> FailableUnit {
+        run(context)
+    }
+}
+
+fn run(context: &Context) -> FailableUnit {
+    let output_dir = outputs::output_dir(context);
+    let config = &context.config.emscripten;
+
+    if context.executor.is_recording() {
+        logs::out(log_tag!(), &format!("Would serve {:?} on {}:{}", &output_dir, &config.bind_address, config.port));
+        return Ok(());
+    }
+
+    let address = web_server::start(&output_dir, &config.bind_address, config.port)?;
+    let host = if address.ip().is_unspecified() {
+        "localhost".to_owned()
+    } else {
+        address.ip().to_string()
+    };
+    let url = format!("http://{}:{}", host, address.port());
+    logs::out(log_tag!(), &format!("Serving {:?} at {} - press Ctrl+C to stop.", &output_dir, &url));
+
+    if config.open_browser {
+        open_in_browser(context, &url);
+    }
+
+    if config.watch {
+        watch(context)
+    } else {
+        loop {
+            std::thread::park();
+        }
+    }
+}
+
+fn watch(context: &Context) -> FailableUnit {
+    let source_fingerprint = || -> Failable<String> { Ok(Fingerprint::new().dir(&context.source_dir.join("src"))?.finish()) };
+    let assets_fingerprint = || -> Failable<String> { Ok(Fingerprint::new().dir(&context.assets_dir)?.finish()) };
+
+    logs::out(log_tag!(), "Watching source code and assets for changes ...");
+    let mut last_source_fingerprint = source_fingerprint()?;
+    let mut last_assets_fingerprint = assets_fingerprint()?;
+
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+
+        let source_fingerprint = source_fingerprint()?;
+        let assets_fingerprint = assets_fingerprint()?;
+        let assets_changed = assets_fingerprint != last_assets_fingerprint;
+
+        if source_fingerprint == last_source_fingerprint && !assets_changed {
+            continue;
+        }
+
+        last_source_fingerprint = source_fingerprint;
+        last_assets_fingerprint = assets_fingerprint;
+        logs::out(log_tag!(), "Change detected, rebuilding ...");
+
+        // A failed rebuild (for example a compile error while editing) shouldn't stop the server, the next change will try again.
+        let rebuild = || -> FailableUnit {
+            assets::process(context)?;
+
+            if assets_changed {
+                invalidate_link(context)?;
+            }
+
+            compile_application(context)?;
+            build_target::create_output(&EmscriptenTarget, context)
+        };
+
+        match rebuild() {
+            Ok(_) => logs::out(log_tag!(), "Rebuild complete, refresh the browser to see the changes."),
+            Err(error) => logs::out(log_tag!(), &format!("Rebuild failed: {:?}", error)),
+        }
+    }
+}
+
+// The asset pack is baked into the '.data' file when emcc links, but Cargo knows nothing about the pack and so wouldn't link again
+// when only the assets changed. Removing the linked output makes Cargo treat the binary as stale, which links a fresh '.data'.
+fn invalidate_link(context: &Context) -> FailableUnit {
+    let build_variant_dir = build_variant_dir(context);
+
+    for extension in &["js", "wasm", "data"] {
+        io::delete(context, &build_variant_dir.join(format!("{}.{}", &context.binary_name, extension)))?;
+    }
 
     Ok(())
 }
 
-fn install_rust_dependencies() -> FailableUnit {
+fn build_variant_dir(context: &Context) -> PathBuf {
+    context.rust_build_dir.join("wasm32-unknown-emscripten").join(context.profile.id()).join("deps")
+}
+
+fn install_rust_dependencies(context: &Context) -> FailableUnit {
     logs::out(log_tag!(), "Installing Rust dependencies ...");
 
-    scripts::run(&Script::new("rustup target add wasm32-unknown-emscripten"))?;
-    scripts::run(&Script::new("cargo install --version 1.12.0 https"))
+    scripts::run(context, &Script::new("rustup target add wasm32-unknown-emscripten").retries(2))
 }
 
-fn sdk_dir_name() -> String {
-    format!("emscripten-sdk-{}", EMSCRIPTEN_VERSION)
+fn sdk_dir_name(context: &Context) -> String {
+    format!("emscripten-sdk-{}", &context.config.emscripten.version)
 }
 
-fn sdk_dir(context: &Context) -> PathBuf {
-    context.working_dir.join(&sdk_dir_name())
+pub fn sdk_dir(context: &Context) -> PathBuf {
+    context.working_dir.join(sdk_dir_name(context))
 }
 
 fn configure_emscripten_sdk(context: &Context) -> FailableUnit {
     logs::out(log_tag!(), "Configuring Emscripten SDK - this may take a while ...");
 
     let emsdk = sdk_dir(context).join("emsdk");
+    let version = &context.config.emscripten.version;
 
-    remote_zips::fetch(EMSCRIPTEN_URL, &sdk_dir_name(), &context.working_dir)?;
-    scripts::run(&Script::new(&format!("{:?} install {}", &emsdk, EMSCRIPTEN_VERSION)))?;
-    scripts::run(&Script::new(&format!("{:?} activate {}", &emsdk, EMSCRIPTEN_VERSION)))
+    remote_zips::fetch(context, &context.config.emscripten.sdk_url, &sdk_dir_name(context), &context.working_dir)?;
+
+    let fingerprint = Fingerprint::new().value(version).value(&context.config.emscripten.sdk_url).output(&sdk_dir(context));
+
+    fingerprints::run_step(context, "emscripten-sdk", &fingerprint, &mut || {
+        scripts::run(
+            context,
+            &Script::new(&format!("{:?} install {}", &emsdk, version))
+                .retries(2)
+                .log_file(&scripts::log_file(context, "emsdk-install")),
+        )?;
+        scripts::run(context, &Script::new(&format!("{:?} activate {}", &emsdk, version)))
+    })
 }
 
 fn compile_application(context: &Context) -> FailableUnit {
     logs::out(log_tag!(), "Compiling application ...");
 
     let emscripten_flags = format!(
-        r#"-s EXPORTED_FUNCTIONS='["_main","_fileno"]' -O2 -s USE_SDL=2 -s USE_SDL_IMAGE=2 -s SDL2_IMAGE_FORMATS='["png"]' -s USE_WEBGL2=1 --preload-file {:?}@/assets"#,
-        &context.assets_dir
+        r#"{} --preload-file {:?}@/{}"#,
+        &context.config.emscripten.flags,
+        &assets::pack_path(context),
+        assets::PACK_FILE_NAME
     );
 
     let mut environment = HashMap::new();
@@ -59,62 +200,107 @@ fn compile_application(context: &Context) -> FailableUnit {
     } else {
         ". ./emsdk_env.sh &&"
     };
-    
+
     scripts::run(
+        context,
         &Script::new(&format!(
-            r#"{} cargo rustc {} --manifest-path {:?} --target wasm32-unknown-emscripten --bin crust --target-dir {:?}"#,
+            r#"{} cargo rustc {} --manifest-path {:?} --target wasm32-unknown-emscripten --bin {} --target-dir {:?}"#,
             &script_prefix,
-            context.variant.rust_compiler_flag(),
+            context.cargo_build_flags(),
             context.source_dir.join("Cargo.toml"),
+            &context.binary_name,
             context.rust_build_dir,
         ))
         .working_dir(&sdk_dir(context))
-        .environment(&environment),
+        .environment(&environment)
+        .log_file(&scripts::log_file(context, "compile")),
     )
 }
 
-fn create_output(context: &Context) -> FailableUnit {
-    logs::out(log_tag!(), "Creating output ...");
+fn html_template_file(context: &Context) -> Option<PathBuf> {
+    // A project template path is relative to the directory holding the config file, which is the project root.
+    let root_dir = context.config_file.parent()?;
+    context.config.emscripten.html_template.as_ref().map(|template| root_dir.join(template))
+}
 
-    let build_variant_dir = context.rust_build_dir.join("wasm32-unknown-emscripten").join(context.variant.id()).join("deps");
+fn render_index_html(context: &Context, html_template_file: &Option<PathBuf>) -> Failable<String> {
+    let config = &context.config.emscripten;
 
-    outputs::clean(context)?;
-    outputs::collect(
-        context,
-        vec![
-            build_variant_dir.join("crust.wasm"),
-            build_variant_dir.join("crust.data"),
-            build_variant_dir.join("crust.js"),
-        ],
-    )?;
-
-    io::write_string(INDEX_HTML_TEMPLATE, &outputs::output_dir(context).join("index.html"))
+    let template = match html_template_file {
+        Some(file) if !file.exists() => return Err(format!("Emscripten HTML template not found: {:?}", file).into()),
+        Some(file) => io::read_string(file)?,
+        None => INDEX_HTML_TEMPLATE.to_owned(),
+    };
+
+    Ok(template
+        .replace("${TITLE}", &config.title)
+        .replace("${BINARY_NAME}", &context.binary_name)
+        .replace("${CANVAS_WIDTH}", &config.canvas_width.to_string())
+        .replace("${CANVAS_HEIGHT}", &config.canvas_height.to_string())
+        .replace("${CANVAS_MODE}", config.canvas_mode.id())
+        .replace("${SHOW_CONSOLE}", &config.show_console.to_string()))
 }
 
-fn open_in_browser(context: &Context) -> FailableUnit {
-    let launch_command = if cfg!(target_os = "windows") { "start" } else { "open" };
+fn open_in_browser(context: &Context, url: &str) {
+    let launch_command = if cfg!(target_os = "windows") {
+        "start"
+    } else if cfg!(target_os = "linux") {
+        "xdg-open"
+    } else {
+        "open"
+    };
 
-    scripts::run(&Script::new(&format!("{} http://localhost:8000", &launch_command)))?;
-    scripts::run(&Script::new("http -p 8000").working_dir(&outputs::output_dir(context)))
+    // Not being able to open a browser (for example over SSH) is fine, the url can still be opened by hand.
+    if scripts::run(context, &Script::new(&format!("{} {}", launch_command, url))).is_err() {
+        logs::out(log_tag!(), &format!("Unable to open a web browser, visit {} manually.", url));
+    }
 }
 
-const INDEX_HTML_TEMPLATE: &str = r#"
+// Placeholders:
+//   ${TITLE}          page title
+//   ${BINARY_NAME}    name of the compiled '.js' file to load
+//   ${CANVAS_WIDTH}   canvas width in 'fixed' mode
+//   ${CANVAS_HEIGHT}  canvas height in 'fixed' mode
+//   ${CANVAS_MODE}    one of 'fixed', 'responsive' or 'fullscreen'
+//   ${SHOW_CONSOLE}   'true' to show program output in a text area below the canvas
+//
+// In 'responsive' and 'fullscreen' modes the canvas is sized by CSS to fill the page. SDL watches the canvas CSS size
+// when the browser window is resized and reports it to the application as a window resize event.
+const INDEX_HTML_TEMPLATE: &str = r#"<!doctype html>
 <html>
     <head>
         <meta charset="utf-8">
-        <meta http-equiv="Content-Type" content="text/html; charset=utf-8">
+        <meta name="viewport" content="width=device-width, initial-scale=1">
+        <title>${TITLE}</title>
         <style>
+            body {
+                margin: 0px;
+                background-color: #222222;
+                color: #ffffff;
+                font-family: sans-serif;
+            }
+
             canvas.emscripten {
-                display:block;
+                display: block;
                 border: 0px none;
                 background-color: #333333;
             }
 
+            body.fill {
+                overflow: hidden;
+            }
+
+            body.fill canvas.emscripten {
+                width: 100vw;
+                height: 100vh;
+            }
+
             textarea.emscripten {
                 resize: none;
-                width: 600px;
+                width: 100%;
                 height: 200px;
-                display:block;
+                display: block;
+                box-sizing: border-box;
                 border: 0px none;
                 padding: 8px;
                 background-color: #222222;
@@ -122,26 +308,57 @@ const INDEX_HTML_TEMPLATE: &str = r#"
                 margin: 0px;
             }
 
+            #status {
+                position: absolute;
+                top: 8px;
+                left: 8px;
+            }
+
             .content {
-                border: 1px solid #333333;
                 display: inline-block;
             }
+
+            body.fill .content {
+                display: block;
+            }
         </style>
     </head>
 
     <body>
         <div class="content">
-            <canvas class="emscripten" id="canvas" width="600", height="360" oncontextmenu="event.preventDefault()" tabindex=-1></canvas>
+            <canvas class="emscripten" id="canvas" width="${CANVAS_WIDTH}" height="${CANVAS_HEIGHT}" oncontextmenu="event.preventDefault()" tabindex=-1></canvas>
             <textarea class="emscripten" id="output" rows="8"></textarea>
         </div>
+        <div id="status">Loading ...</div>
 
         <script type='text/javascript'>
+            var canvasMode = '${CANVAS_MODE}';
+            var showConsole = ${SHOW_CONSOLE};
+            var canvas = document.getElementById('canvas');
+            var output = document.getElementById('output');
+            var statusElement = document.getElementById('status');
+
+            if (canvasMode !== 'fixed') {
+                document.body.classList.add('fill');
+                canvas.width = window.innerWidth;
+                canvas.height = window.innerHeight;
+            }
+
+            if (canvasMode === 'fullscreen') {
+                canvas.addEventListener('click', function () {
+                    Module.requestFullscreen(false, true);
+                });
+            }
+
+            if (!showConsole || canvasMode !== 'fixed') {
+                output.style.display = 'none';
+            }
+
             var Module = {
                 preRun: [],
                 postRun: [],
                 print: (function () {
-                    var element = document.getElementById('output');
-                    if (element) element.value = '';
+                    if (output) output.value = '';
                     return function (text) {
                         if (arguments.length > 1) {
                             text = Array.prototype.slice.call(arguments).join(' ');
@@ -149,9 +366,9 @@ const INDEX_HTML_TEMPLATE: &str = r#"
 
                         console.log(text);
 
-                        if (element) {
-                            element.value += text + "\n";
-                            element.scrollTop = element.scrollHeight;
+                        if (showConsole && output) {
+                            output.value += text + "\n";
+                            output.scrollTop = output.scrollHeight;
                         }
                     };
                 })(),
@@ -162,12 +379,24 @@ const INDEX_HTML_TEMPLATE: &str = r#"
 
                     console.error(text);
                 },
-                canvas: (function () {
-                    return document.getElementById('canvas');
-                })()
+                canvas: canvas,
+                setStatus: function (text) {
+                    // Emscripten reports download progress as 'Downloading data... (loaded/total)' and clears the status when running.
+                    statusElement.textContent = text;
+                    statusElement.style.display = text ? 'block' : 'none';
+                },
+                totalDependencies: 0,
+                monitorRunDependencies: function (left) {
+                    this.totalDependencies = Math.max(this.totalDependencies, left);
+                    Module.setStatus(left ? 'Preparing ... (' + (this.totalDependencies - left) + '/' + this.totalDependencies + ')' : '');
+                }
+            };
+
+            window.onerror = function () {
+                Module.setStatus('Something went wrong, see the browser console for details.');
             };
         </script>
-        <script async type="text/javascript" src="crust.js"></script>
+        <script async type="text/javascript" src="${BINARY_NAME}.js"></script>
     </body>
 </html>
 "#;
@@ -0,0 +1,296 @@
+use crate::{
+    core::{
+        assets, build_report,
+        build_target::{BuildTarget, Resolved},
+        context::Context,
+        executor,
+        failable::Failable,
+        failable_unit::FailableUnit,
+        fingerprint::Fingerprint,
+        io, logs, outputs, remote_zips,
+        script::Script,
+        scripts,
+        target::Target,
+        variant::Variant,
+    },
+    linux_packaging, log_tag,
+};
+use std::path::PathBuf;
+
+const SDL2_DIR: &str = "SDL2";
+
+const SDL2_IMAGE_DIR: &str = "SDL2_image";
+
+const SDL2_PREFIX_DIR: &str = "sdl2-prefix";
+
+#[derive(Default)]
+pub struct LinuxTarget {
+    // Where the SDL2 libraries built from source live, or none when linking against the system installed ones.
+    sdl2_libs_dir: Resolved<Option<PathBuf>>,
+}
+
+impl BuildTarget for LinuxTarget {
+    fn target(&self) -> Target {
+        Target::Linux
+    }
+
+    fn is_supported_on_host(&self) -> bool {
+        cfg!(target_os = "linux")
+    }
+
+    fn prepare_dependencies(&self, context: &Context) -> FailableUnit {
+        self.sdl2_libs_dir.set(setup_sdl2(context)?);
+        Ok(())
+    }
+
+    fn compile(&self, context: &Context) -> FailableUnit {
+        compile(context, &*self.sdl2_libs_dir.get()?)
+    }
+
+    fn output_files(&self, context: &Context) -> Failable<Vec<PathBuf>> {
+        Ok(vec![context.rust_build_dir.join(context.profile.id()).join(&context.binary_name)])
+    }
+
+    fn output_fingerprint(&self, context: &Context, fingerprint: Fingerprint) -> Failable<Fingerprint> {
+        let fingerprint = fingerprint.file(&assets::pack_path(context))?;
+
+        match &*self.sdl2_libs_dir.get()? {
+            Some(libs_dir) => fingerprint.dir(libs_dir),
+            None => Ok(fingerprint),
+        }
+    }
+
+    fn complete_output(&self, context: &Context) -> FailableUnit {
+        complete_output(context, &*self.sdl2_libs_dir.get()?)
+    }
+
+    fn prepare_package(&self, context: &Context, stage_dir: &PathBuf) -> FailableUnit {
+        linux_packaging::bundle_libraries(context, stage_dir)
+    }
+
+    fn package(&self, context: &Context, stage_dir: &PathBuf) -> FailableUnit {
+        linux_packaging::create_app_dir(context, stage_dir)?;
+        linux_packaging::create_deb(context, stage_dir)?;
+        Ok(())
+    }
+}
+
+fn setup_sdl2(context: &Context) -> Failable<Option<PathBuf>> {
+    // If the host already has SDL2 and SDL2_image development packages installed (for example via 'apt install libsdl2-dev libsdl2-image-dev')
+    // we will simply link against them and rely on the system to provide the shared libraries at runtime.
+    if executor::probe_command(context, "pkg-config", &["--exists", "sdl2", "SDL2_image"]).is_some() {
+        logs::out(log_tag!(), "Using system installed SDL2 and SDL2_image libraries.");
+
+        for library in &["sdl2", "SDL2_image"] {
+            if let Some(version) = executor::probe_command(context, "pkg-config", &["--modversion", library]) {
+                build_report::record_third_party(context, library, &version, "pkg-config");
+            }
+        }
+
+        return Ok(None);
+    }
+
+    // Otherwise we will build SDL2 and SDL2_image from source into a local prefix directory and bundle their shared libraries with our output.
+    let prefix_dir = context.working_dir.join(SDL2_PREFIX_DIR);
+
+    setup_sdl2_library(context, &prefix_dir)?;
+    setup_sdl2_image_library(context, &prefix_dir)?;
+
+    Ok(Some(prefix_dir.join("lib")))
+}
+
+fn setup_sdl2_library(context: &Context, prefix_dir: &PathBuf) -> FailableUnit {
+    // No need to build if its output already exist.
+    if prefix_dir.join("lib").join("libSDL2.so").exists() {
+        return Ok(());
+    }
+
+    remote_zips::fetch(context, &context.config.linux.sdl2_url, SDL2_DIR, &context.working_dir)?;
+
+    logs::out(log_tag!(), "Compiling SDL2 from source, this may take a while ...");
+    scripts::run(
+        context,
+        &Script::new(&format!("./configure --prefix={:?} && make -j$(nproc) && make install", prefix_dir))
+            .working_dir(&context.working_dir.join(SDL2_DIR))
+            .log_file(&scripts::log_file(context, "sdl2")),
+    )
+}
+
+fn setup_sdl2_image_library(context: &Context, prefix_dir: &PathBuf) -> FailableUnit {
+    // No need to build if its output already exist.
+    if prefix_dir.join("lib").join("libSDL2_image.so").exists() {
+        return Ok(());
+    }
+
+    remote_zips::fetch(context, &context.config.linux.sdl2_image_url, SDL2_IMAGE_DIR, &context.working_dir)?;
+
+    // SDL2_image needs to find the SDL2 we just built rather than any system installation, so we point its configure script at our prefix.
+    logs::out(log_tag!(), "Compiling SDL2_image from source, this may take a while ...");
+    scripts::run(
+        context,
+        &Script::new(&format!(
+            "./configure --prefix={:?} --with-sdl-prefix={:?} && make -j$(nproc) && make install",
+            prefix_dir, prefix_dir
+        ))
+        .working_dir(&context.working_dir.join(SDL2_IMAGE_DIR))
+        .log_file(&scripts::log_file(context, "sdl2-image")),
+    )
+}
+
+fn compile(context: &Context, sdl2_libs_dir: &Option<PathBuf>) -> FailableUnit {
+    logs::out(log_tag!(), "Compiling application ...");
+
+    // When SDL2 was built from source we add a linker search path to our local prefix and embed an rpath so the
+    // binary will look for its shared libraries in a 'lib' directory next to itself, similar to how the MacOS
+    // console target uses '@loader_path/Frameworks'. Note the single quotes to stop the shell expanding '$ORIGIN'.
+    let linker_args = match sdl2_libs_dir {
+        Some(libs_dir) => format!(r#"-- -L {:?} -C link-arg='-Wl,-rpath,$ORIGIN/lib'"#, libs_dir),
+        None => String::new(),
+    };
+
+    scripts::run(
+        context,
+        &Script::new(&format!(
+            r#"cargo rustc {} --manifest-path {:?} --bin {} --target-dir {:?} {}"#,
+            context.cargo_build_flags(),
+            context.source_dir.join("Cargo.toml"),
+            &context.binary_name,
+            context.rust_build_dir,
+            linker_args,
+        ))
+        .log_file(&scripts::log_file(context, "compile")),
+    )?;
+
+    logs::out(log_tag!(), "Compile completed successfully!");
+
+    Ok(())
+}
+
+fn complete_output(context: &Context, sdl2_libs_dir: &Option<PathBuf>) -> FailableUnit {
+    outputs::link_or_collect(context, &assets::pack_path(context), assets::PACK_FILE_NAME)?;
+
+    if let Some(libs_dir) = sdl2_libs_dir {
+        match context.variant {
+            Variant::Debug => outputs::link_or_collect(context, libs_dir, "lib")?,
+            Variant::Release => collect_sdl2_libraries(context, &outputs::output_dir(context).join("lib"), libs_dir)?,
+        }
+    }
+
+    Ok(())
+}
+
+// The prefix also holds static archives, libtool files and pkg-config / CMake metadata which are only needed for linking,
+// so only the shared libraries (and their version symlinks) are shipped.
+fn collect_sdl2_libraries(context: &Context, output_libs_dir: &PathBuf, libs_dir: &PathBuf) -> FailableUnit {
+    io::create_dir(context, output_libs_dir)?;
+
+    context.executor.file_operation("copy_sdl2_libraries", Some(libs_dir), output_libs_dir, &mut || {
+        for entry in std::fs::read_dir(libs_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if name.starts_with("libSDL2") && name.contains(".so") {
+                io::copy(context, &entry.path(), &output_libs_dir.join(&name))?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{build_plan, build_plan::RecordingExecutor, build_target, context::Options};
+    use std::{collections::BTreeMap, rc::Rc};
+
+    // Plans a debug build with every probe answered from 'answers', with the target's own directories moved into a temp
+    // dir so files left behind by a real build can't change the plan. Paths are replaced with placeholders in the result.
+    fn plan(answers: &[(&str, &str)]) -> Vec<String> {
+        let root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let recorder = Rc::new(RecordingExecutor::new(
+            answers.iter().map(|(name, answer)| (name.to_string(), answer.to_string())).collect::<BTreeMap<_, _>>(),
+            false,
+        ));
+
+        let options = Options {
+            executor: recorder.clone(),
+            ..Options::new(Target::Linux, Variant::Debug)
+        };
+        let mut context = Context::new(root_dir.clone(), options).unwrap();
+        context.target_home_dir = temp_dir.path().join("linux");
+        context.working_dir = context.target_home_dir.join(".rust-build");
+        context.rust_build_dir = context.working_dir.join("rust");
+
+        build_target::build(&LinuxTarget::default(), &context).unwrap();
+
+        build_plan::render(&recorder.steps())
+            .replace(&context.target_home_dir.to_string_lossy().to_string(), "<target-home>")
+            .replace(&root_dir.to_string_lossy().to_string(), "<root>")
+            .lines()
+            .map(|line| line.to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn plans_against_system_sdl2() {
+        let plan = plan(&[
+            ("pkg-config --exists sdl2 SDL2_image", ""),
+            ("pkg-config --modversion sdl2", "2.0.20"),
+            ("pkg-config --modversion SDL2_image", "2.0.5"),
+        ]);
+
+        assert_eq!(
+            plan,
+            vec![
+                r#"[1] probe: pkg-config --exists sdl2 SDL2_image => Some("")"#,
+                r#"[2] probe: pkg-config --modversion sdl2 => Some("2.0.20")"#,
+                r#"[3] probe: pkg-config --modversion SDL2_image => Some("2.0.5")"#,
+                r#"[4] delete: "<target-home>/.rust-build/fingerprints/assets""#,
+                r#"[5] process_assets: "<root>/crust-main/assets" => "<target-home>/.rust-build/assets.pack""#,
+                r#"[6] write: "<target-home>/.rust-build/fingerprints/assets""#,
+                r#"[7] script"#,
+                r#"    > cargo rustc  --manifest-path "<root>/crust-main/Cargo.toml" --bin crust --target-dir "<target-home>/.rust-build/rust""#,
+                r#"[8] delete: "<target-home>/.rust-build/fingerprints/collect-outputs-debug""#,
+                r#"[9] delete: "<target-home>/out/debug""#,
+                r#"[10] create_dir: "<target-home>/out/debug""#,
+                r#"[11] copy: "<target-home>/.rust-build/rust/debug/crust" => "<target-home>/out/debug/crust""#,
+                r#"[12] symlink: "<target-home>/.rust-build/assets.pack" => "<target-home>/out/debug/assets.pack""#,
+                r#"[13] write: "<target-home>/.rust-build/fingerprints/collect-outputs-debug""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn plans_sdl2_from_source_without_system_sdl2() {
+        let plan = plan(&[]);
+
+        assert_eq!(
+            plan,
+            vec![
+                r#"[1] probe: pkg-config --exists sdl2 SDL2_image => None"#,
+                r#"[2] download: https://www.libsdl.org/release/SDL2-2.0.14.zip => "<target-home>/.rust-build/SDL2""#,
+                r#"[3] script"#,
+                r#"    working dir: "<target-home>/.rust-build/SDL2""#,
+                r#"    > ./configure --prefix="<target-home>/.rust-build/sdl2-prefix" && make -j$(nproc) && make install"#,
+                r#"[4] download: https://www.libsdl.org/projects/SDL_image/release/SDL2_image-2.0.5.zip => "<target-home>/.rust-build/SDL2_image""#,
+                r#"[5] script"#,
+                r#"    working dir: "<target-home>/.rust-build/SDL2_image""#,
+                r#"    > ./configure --prefix="<target-home>/.rust-build/sdl2-prefix" --with-sdl-prefix="<target-home>/.rust-build/sdl2-prefix" && make -j$(nproc) && make install"#,
+                r#"[6] delete: "<target-home>/.rust-build/fingerprints/assets""#,
+                r#"[7] process_assets: "<root>/crust-main/assets" => "<target-home>/.rust-build/assets.pack""#,
+                r#"[8] write: "<target-home>/.rust-build/fingerprints/assets""#,
+                r#"[9] script"#,
+                r#"    > cargo rustc  --manifest-path "<root>/crust-main/Cargo.toml" --bin crust --target-dir "<target-home>/.rust-build/rust" -- -L "<target-home>/.rust-build/sdl2-prefix/lib" -C link-arg='-Wl,-rpath,$ORIGIN/lib'"#,
+                r#"[10] delete: "<target-home>/.rust-build/fingerprints/collect-outputs-debug""#,
+                r#"[11] delete: "<target-home>/out/debug""#,
+                r#"[12] create_dir: "<target-home>/out/debug""#,
+                r#"[13] copy: "<target-home>/.rust-build/rust/debug/crust" => "<target-home>/out/debug/crust""#,
+                r#"[14] symlink: "<target-home>/.rust-build/assets.pack" => "<target-home>/out/debug/assets.pack""#,
+                r#"[15] symlink: "<target-home>/.rust-build/sdl2-prefix/lib" => "<target-home>/out/debug/lib""#,
+                r#"[16] write: "<target-home>/.rust-build/fingerprints/collect-outputs-debug""#,
+            ]
+        );
+    }
+}
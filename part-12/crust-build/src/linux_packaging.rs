@@ -0,0 +1,219 @@
+use crate::{
+    core::{
+        context::Context, failable::Failable, failable_unit::FailableUnit, io, logs, manifests, outputs, script::Script, scripts,
+        versions,
+    },
+    log_tag,
+};
+use std::path::PathBuf;
+
+// Only SDL is bundled, everything else (libc, OpenGL drivers etc) must come from the system the package is installed on.
+const BUNDLED_LIBRARY_PREFIXES: &[&str] = &["libSDL2-", "libSDL2_image-"];
+
+#[allow(clippy::ptr_arg)]
+pub fn bundle_libraries(context: &Context, stage_dir: &PathBuf) -> FailableUnit {
+    let binary_path = stage_dir.join(&context.binary_name);
+    let libs_dir = stage_dir.join("lib");
+
+    // SDL built from source is already in the output 'lib' directory and the binary already has an rpath pointing at it.
+    if libs_dir.exists() {
+        return remove_unlinked_libraries(context, &binary_path, &libs_dir);
+    }
+
+    copy_linked_libraries(context, &binary_path, &libs_dir)?;
+
+    // The same trick as the MacOS console target's '@loader_path/Frameworks' rpath, the binary looks in 'lib' next to itself.
+    logs::out(log_tag!(), "Setting rpath of binary to '$ORIGIN/lib' ...");
+    scripts::run(context, &Script::new(&format!("patchelf --set-rpath '$ORIGIN/lib' {:?}", &binary_path)))
+}
+
+fn copy_linked_libraries(context: &Context, binary_path: &PathBuf, libs_dir: &PathBuf) -> FailableUnit {
+    // Otherwise the binary links against the system SDL, so we copy the exact libraries it resolves to next to it.
+    io::create_dir(context, libs_dir)?;
+
+    for (soname, path) in bundled_libraries(context, binary_path)? {
+        logs::out(log_tag!(), &format!("Bundling shared library: {:?}", &path));
+        io::copy(context, &path.canonicalize()?, &libs_dir.join(soname))?;
+    }
+
+    Ok(())
+}
+
+fn remove_unlinked_libraries(context: &Context, binary_path: &PathBuf, libs_dir: &PathBuf) -> FailableUnit {
+    // Staging resolves the development and versioned symlinks next to each SONAME into full copies of the same library, the
+    // binary only ever loads the SONAME so the rest would just be shipped as duplicates.
+    let sonames: Vec<String> = bundled_libraries(context, binary_path)?.into_iter().map(|(soname, _)| soname).collect();
+
+    for library in io::list_files(libs_dir)? {
+        let name = library.file_name().ok_or("Missing file name")?.to_string_lossy().to_string();
+
+        if !sonames.contains(&name) {
+            io::delete(context, &library)?;
+        }
+    }
+
+    Ok(())
+}
+
+// The SONAME and resolved path of every bundled library the binary links against.
+fn bundled_libraries(context: &Context, binary_path: &PathBuf) -> Failable<Vec<(String, PathBuf)>> {
+    let output = scripts::run_captured(context, &Script::new(&format!("ldd {:?}", binary_path)).quiet())
+        .map_err(|error| format!("Failed to inspect shared libraries of {:?}: {}", &binary_path, error))?;
+
+    // Lines look like: 'libSDL2-2.0.so.0 => /usr/lib/x86_64-linux-gnu/libSDL2-2.0.so.0 (0x00007f...)'
+    Ok(output
+        .stdout
+        .lines()
+        .filter_map(|line| match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [soname, "=>", path, ..] if BUNDLED_LIBRARY_PREFIXES.iter().any(|prefix| soname.starts_with(prefix)) => {
+                Some((soname.to_string(), PathBuf::from(path)))
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+pub fn create_app_dir(context: &Context, stage_dir: &PathBuf) -> Failable<PathBuf> {
+    let binary_name = &context.binary_name;
+    let name = stage_dir.file_name().ok_or("Missing file name")?.to_string_lossy();
+    let app_dir = outputs::outputs_root_dir(context).join(format!("{}.AppDir", name));
+    let bin_dir = app_dir.join("usr").join("bin");
+
+    logs::out(log_tag!(), &format!("Creating AppDir: {:?}", &app_dir));
+
+    io::delete(context, &app_dir)?;
+    io::copy_dir_contents(context, stage_dir, &bin_dir)?;
+
+    let app_run = app_dir.join("AppRun");
+    io::write_string(
+        context,
+        &format!("#!/bin/sh\nHERE=\"$(dirname \"$(readlink -f \"$0\")\")\"\nexec \"$HERE/usr/bin/{}\" \"$@\"\n", binary_name),
+        &app_run,
+    )?;
+    io::apply_permissions(context, &app_run, 0o755)?;
+
+    io::write_string(context, &desktop_entry(context), &app_dir.join(format!("{}.desktop", binary_name)))?;
+
+    match &context.config.linux.icon {
+        Some(icon) => io::copy(
+            context,
+            &context.config_file.parent().ok_or("Missing parent dir")?.join(icon),
+            &app_dir.join(format!("{}.png", binary_name)),
+        )?,
+        None => logs::out(log_tag!(), "No 'linux.icon' configured, AppImage tools will need an icon added to the AppDir."),
+    }
+
+    Ok(app_dir)
+}
+
+pub fn create_deb(context: &Context, stage_dir: &PathBuf) -> Failable<PathBuf> {
+    let binary_name = &context.binary_name;
+    let version = versions::cargo_version(context)?;
+    let architecture = deb_architecture()?;
+    let deb_path = outputs::outputs_root_dir(context).join(format!("{}_{}_{}.deb", binary_name, version, architecture));
+
+    context.executor.file_operation("deb", Some(stage_dir), &deb_path, &mut || {
+        write_deb(context, stage_dir, &deb_path, &version, architecture)
+    })?;
+
+    Ok(deb_path)
+}
+
+fn write_deb(context: &Context, stage_dir: &PathBuf, deb_path: &PathBuf, version: &str, architecture: &str) -> FailableUnit {
+    let binary_name = &context.binary_name;
+
+    logs::out(log_tag!(), &format!("Creating Debian package: {:?}", deb_path));
+
+    // The application is installed into '/opt/<name>' as is, with a launcher in '/usr/bin' that runs it from there.
+    let install_dir = format!("./opt/{}", binary_name);
+    let launcher = format!("#!/bin/sh\nexec /opt/{}/{} \"$@\"\n", binary_name, binary_name);
+
+    let mut data = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+    data.follow_symlinks(true);
+    append_dir(&mut data, "./opt/")?;
+    data.append_dir_all(&install_dir, stage_dir)?;
+    append_dir(&mut data, "./usr/")?;
+    append_dir(&mut data, "./usr/bin/")?;
+    append_file(&mut data, &format!("./usr/bin/{}", binary_name), launcher.as_bytes(), 0o755)?;
+    let data = data.into_inner()?.finish()?;
+
+    let mut installed_size: u64 = launcher.len() as u64;
+    for file in io::list_files(stage_dir)? {
+        installed_size += std::fs::metadata(&file)?.len();
+    }
+
+    let control_file = format!(
+        "Package: {}\nVersion: {}\nArchitecture: {}\nMaintainer: {}\nInstalled-Size: {}\nDescription: {}\n",
+        binary_name.to_lowercase().replace('_', "-"),
+        version,
+        architecture,
+        maintainer(context)?,
+        installed_size / 1024 + 1,
+        &context.config.linux.description,
+    );
+
+    let mut control = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+    append_file(&mut control, "./control", control_file.as_bytes(), 0o644)?;
+    let control = control.into_inner()?.finish()?;
+
+    // A .deb is an 'ar' archive whose members must appear in exactly this order.
+    io::create_dir(context, &outputs::outputs_root_dir(context))?;
+    let mut deb = ar::Builder::new(std::fs::File::create(deb_path)?);
+    append_member(&mut deb, "debian-binary", b"2.0\n")?;
+    append_member(&mut deb, "control.tar.gz", &control)?;
+    append_member(&mut deb, "data.tar.gz", &data)
+}
+
+fn desktop_entry(context: &Context) -> String {
+    let binary_name = &context.binary_name;
+
+    format!(
+        "[Desktop Entry]\nType=Application\nName={}\nComment={}\nExec={}\nIcon={}\nCategories=Game;\nTerminal=false\n",
+        binary_name, &context.config.linux.description, binary_name, binary_name
+    )
+}
+
+fn deb_architecture() -> Failable<&'static str> {
+    match std::env::consts::ARCH {
+        "x86_64" => Ok("amd64"),
+        "x86" => Ok("i386"),
+        "aarch64" => Ok("arm64"),
+        "arm" => Ok("armhf"),
+        other => Err(format!("No Debian architecture known for host architecture '{}'", other).into()),
+    }
+}
+
+fn maintainer(context: &Context) -> Failable<String> {
+    Ok(manifests::package_field(&context.source_dir, "authors")?
+        .as_array()
+        .and_then(|authors| authors.get(0))
+        .and_then(|author| author.as_str())
+        .unwrap_or("Unknown")
+        .to_owned())
+}
+
+fn append_dir<W: std::io::Write>(archive: &mut tar::Builder<W>, path: &str) -> FailableUnit {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Directory);
+    header.set_mode(0o755);
+    header.set_size(0);
+    header.set_cksum();
+
+    Ok(archive.append_data(&mut header, path, std::io::empty())?)
+}
+
+fn append_file<W: std::io::Write>(archive: &mut tar::Builder<W>, path: &str, content: &[u8], mode: u32) -> FailableUnit {
+    let mut header = tar::Header::new_gnu();
+    header.set_mode(mode);
+    header.set_size(content.len() as u64);
+    header.set_cksum();
+
+    Ok(archive.append_data(&mut header, path, content)?)
+}
+
+fn append_member(archive: &mut ar::Builder<std::fs::File>, name: &str, content: &[u8]) -> FailableUnit {
+    let mut header = ar::Header::new(name.as_bytes().to_vec(), content.len() as u64);
+    header.set_mode(0o644);
+
+    Ok(archive.append(&header, content)?)
+}
@@ -1,57 +1,70 @@
 use crate::{
-    core::{context::Context, failable_unit::FailableUnit, io, logs, outputs, script::Script, scripts, variant::Variant},
+    core::{
+        assets, build_target::BuildTarget, context::Context, failable::Failable, failable_unit::FailableUnit,
+        fingerprint::Fingerprint, logs, outputs, script::Script, scripts, target::Target,
+    },
     log_tag, macos_sdl,
 };
 use std::path::PathBuf;
 
-pub fn build(context: &Context) -> FailableUnit {
-    context.print_summary();
-    let frameworks_dir = macos_sdl::setup(context)?;
-    compile(context, &frameworks_dir)?;
-    create_output(context, &frameworks_dir)
-}
+pub struct MacOSConsoleTarget;
 
-fn compile(context: &Context, frameworks_dir: &PathBuf) -> FailableUnit {
-    logs::out(log_tag!(), "Compiling application ...");
+impl BuildTarget for MacOSConsoleTarget {
+    fn target(&self) -> Target {
+        Target::MacOSConsole
+    }
 
-    scripts::run(&Script::new(&format!(
-        r#"cargo rustc {} --manifest-path {:?} --bin crust --target-dir {:?} -- -L framework={:?}"#,
-        context.variant.rust_compiler_flag(),
-        context.source_dir.join("Cargo.toml"),
-        context.rust_build_dir,
-        frameworks_dir,
-    )))?;
+    fn is_supported_on_host(&self) -> bool {
+        cfg!(target_os = "macos")
+    }
 
-    logs::out(log_tag!(), "Compile completed successfully!");
+    fn prepare_dependencies(&self, context: &Context) -> FailableUnit {
+        macos_sdl::setup(context)?;
+        Ok(())
+    }
 
-    Ok(())
-}
+    fn compile(&self, context: &Context) -> FailableUnit {
+        compile(context, &macos_sdl::frameworks_dir(context))
+    }
 
-fn create_output(context: &Context, frameworks_dir: &PathBuf) -> FailableUnit {
-    let output_dir = outputs::output_dir(context);
+    fn output_files(&self, context: &Context) -> Failable<Vec<PathBuf>> {
+        Ok(vec![context.rust_build_dir.join(context.profile.id()).join(&context.binary_name)])
+    }
 
-    logs::out(log_tag!(), "Creating product ...");
+    fn output_fingerprint(&self, context: &Context, fingerprint: Fingerprint) -> Failable<Fingerprint> {
+        fingerprint.file(&assets::pack_path(context))?.dir(&macos_sdl::frameworks_dir(context))
+    }
 
-    outputs::clean(context)?;
-    outputs::collect(context, vec![context.rust_build_dir.join(context.variant.id()).join("crust")])?;
+    fn complete_output(&self, context: &Context) -> FailableUnit {
+        complete_output(context, &macos_sdl::frameworks_dir(context))
+    }
+}
+
+fn compile(context: &Context, frameworks_dir: &PathBuf) -> FailableUnit {
+    logs::out(log_tag!(), "Compiling application ...");
 
-    match context.variant {
-        Variant::Debug => {
-            logs::out(log_tag!(), "Debug build - symlinking assets ...");
-            io::create_symlink(&context.assets_dir, &PathBuf::from("assets"), &output_dir)?;
+    scripts::run(
+        context,
+        &Script::new(&format!(
+            r#"cargo rustc {} --manifest-path {:?} --bin {} --target-dir {:?} -- -L framework={:?}"#,
+            context.cargo_build_flags(),
+            context.source_dir.join("Cargo.toml"),
+            &context.binary_name,
+            context.rust_build_dir,
+            frameworks_dir,
+        ))
+        .log_file(&scripts::log_file(context, "compile")),
+    )?;
 
-            logs::out(log_tag!(), "Debug build - symlinking frameworks ...");
-            io::create_symlink(&frameworks_dir, &PathBuf::from("Frameworks"), &output_dir)?;
-        }
+    logs::out(log_tag!(), "Compile completed successfully!");
 
-        Variant::Release => {
-            logs::out(log_tag!(), "Release build - copying assets ...");
-            outputs::collect(context, vec![context.assets_dir.clone()])?;
+    Ok(())
+}
 
-            logs::out(log_tag!(), "Release build - copying frameworks ...");
-            outputs::collect(context, vec![frameworks_dir.clone()])?;
-        }
-    }
+fn complete_output(context: &Context, frameworks_dir: &PathBuf) -> FailableUnit {
+    outputs::link_or_collect(context, &assets::pack_path(context), assets::PACK_FILE_NAME)?;
+    outputs::link_or_collect(context, frameworks_dir, "Frameworks")?;
 
-    scripts::run(&Script::new("install_name_tool -add_rpath @loader_path/Frameworks crust").working_dir(&output_dir))
+    let add_rpath = format!("install_name_tool -add_rpath @loader_path/Frameworks {}", &context.binary_name);
+    scripts::run(context, &Script::new(&add_rpath).working_dir(&outputs::output_dir(context)))
 }
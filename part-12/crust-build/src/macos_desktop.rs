@@ -1,45 +1,82 @@
 use crate::{
-    core::{context::Context, failable_unit::FailableUnit, io, logs, script::Script, scripts},
+    core::{
+        build_target::{self, BuildTarget},
+        context::Context,
+        failable_unit::FailableUnit,
+        io, logs,
+        script::Script,
+        scripts,
+        target::Target,
+    },
     log_tag, macos_sdl,
 };
 use std::path::PathBuf;
 
-const ARCHITECTURE_X86_64: &str = "x86_64-apple-darwin";
-const ARCHITECTURE_ARM64: &str = "aarch64-apple-darwin";
+pub struct MacOSDesktopTarget;
 
-pub fn build(context: &Context) -> FailableUnit {
-    context.print_summary();
+impl BuildTarget for MacOSDesktopTarget {
+    fn target(&self) -> Target {
+        Target::MacOSDesktop
+    }
 
-    install_rust_dependencies()?;
+    fn is_supported_on_host(&self) -> bool {
+        cfg!(target_os = "macos")
+    }
 
-    let frameworks_dir = macos_sdl::setup(context)?;
-    link_frameworks(context, &frameworks_dir)?;
-    compile(context)?;
-    create_output(context)?;
+    fn prepare_dependencies(&self, context: &Context) -> FailableUnit {
+        install_rust_dependencies(context)?;
 
-    Ok(())
+        let frameworks_dir = macos_sdl::setup(context)?;
+        link_frameworks(context, &frameworks_dir)
+    }
+
+    // The Xcode project bundles 'crust-main/assets' itself, so its shaders are never validated and the application loads
+    // the source '.obj' and '.png' files in place of processed ones (see 'crust-main/src/core/io.rs').
+    fn process_assets(&self, _context: &Context) -> FailableUnit {
+        Ok(())
+    }
+
+    fn compile(&self, context: &Context) -> FailableUnit {
+        compile(context)
+    }
+
+    fn create_output(&self, context: &Context) -> FailableUnit {
+        create_output(context)
+    }
+
+    fn run(&self, context: &Context) -> FailableUnit {
+        build_target::xcode_only(context)
+    }
 }
 
-fn install_rust_dependencies() -> FailableUnit {
-    scripts::run(&Script::new(&format!("rustup target add {} {}", ARCHITECTURE_X86_64, ARCHITECTURE_ARM64)))
+fn install_rust_dependencies(context: &Context) -> FailableUnit {
+    scripts::run(
+        context,
+        &Script::new(&format!("rustup target add {}", context.config.macos.desktop_architectures.join(" "))).retries(2),
+    )
 }
 
 fn link_frameworks(context: &Context, frameworks_dir: &PathBuf) -> FailableUnit {
-    io::create_symlink(frameworks_dir, &context.target_home_dir.join("crust").join("Frameworks"), &context.working_dir)
+    io::create_symlink(context, frameworks_dir, &context.target_home_dir.join("crust").join("Frameworks"), &context.working_dir)
 }
 
 fn compile(context: &Context) -> FailableUnit {
-    for architecture in &vec![ARCHITECTURE_X86_64, ARCHITECTURE_ARM64] {
+    for architecture in &context.config.macos.desktop_architectures {
         logs::out(log_tag!(), &format!("Compiling architecture: {} ...", &architecture));
 
-        scripts::run(&Script::new(&format!(
-            "cargo rustc {} --manifest-path {:?} --target {} --bin crust --target-dir {:?} -- -L framework={:?}",
-            context.variant.rust_compiler_flag(),
-            context.source_dir.join("Cargo.toml"),
-            &architecture,
-            context.rust_build_dir,
-            context.working_dir.join("Frameworks"),
-        )))?;
+        scripts::run(
+            context,
+            &Script::new(&format!(
+                "cargo rustc {} --manifest-path {:?} --target {} --bin {} --target-dir {:?} -- -L framework={:?}",
+                context.cargo_build_flags(),
+                context.source_dir.join("Cargo.toml"),
+                &architecture,
+                &context.binary_name,
+                context.rust_build_dir,
+                macos_sdl::frameworks_dir(context),
+            ))
+            .log_file(&scripts::log_file(context, &format!("compile-{}", architecture))),
+        )?;
     }
 
     Ok(())
@@ -47,13 +84,24 @@ fn compile(context: &Context) -> FailableUnit {
 
 fn create_output(context: &Context) -> FailableUnit {
     let output_binary_dir = context.target_home_dir.join("crust").join("crust");
-    let x86_64_binary = context.rust_build_dir.join(ARCHITECTURE_X86_64).join(context.variant.id()).join("crust");
-    let arm64_binary = context.rust_build_dir.join(ARCHITECTURE_ARM64).join(context.variant.id()).join("crust");
+    let architecture_binaries: Vec<String> = context
+        .config
+        .macos
+        .desktop_architectures
+        .iter()
+        .map(|architecture| {
+            format!("{:?}", context.rust_build_dir.join(architecture).join(context.profile.id()).join(&context.binary_name))
+        })
+        .collect();
 
+    // The Xcode project always expects the universal binary to be named 'crust' regardless of the name of the Rust binary.
     scripts::run(
-        &Script::new(&format!("lipo -create -output crust {:?} {:?}", &x86_64_binary, &arm64_binary))
-            .working_dir(&output_binary_dir),
+        context,
+        &Script::new(&format!("lipo -create -output crust {}", architecture_binaries.join(" "))).working_dir(&output_binary_dir),
     )?;
 
-    scripts::run(&Script::new("install_name_tool -add_rpath @loader_path/../Frameworks crust").working_dir(&output_binary_dir))
+    scripts::run(
+        context,
+        &Script::new("install_name_tool -add_rpath @loader_path/../Frameworks crust").working_dir(&output_binary_dir),
+    )
 }
@@ -4,41 +4,45 @@ use crate::{
 };
 use std::path::PathBuf;
 
-const SDL2_URL: &str = "https://www.libsdl.org/release/SDL2-2.0.14.zip";
 const SDL2_DIR: &str = "SDL2";
 const SDL2_FRAMEWORK_NAME: &str = "SDL2.framework";
 
-const SDL2_IMAGE_URL: &str = "https://www.libsdl.org/projects/SDL_image/release/SDL2_image-2.0.5.zip";
 const SDL2_IMAGE_DIR: &str = "SDL2_image";
 const SDL2_IMAGE_FRAMEWORK_NAME: &str = "SDL2_image.framework";
 const SDL2_IMAGE_CUSTOM_FRAMEWORK_DIR: &str = "SDL2_image_custom_framework";
 
+pub fn frameworks_dir(context: &Context) -> PathBuf {
+    context.working_dir.join("Frameworks")
+}
+
 pub fn setup(context: &Context) -> Failable<PathBuf> {
-    let frameworks_dir = context.working_dir.join("Frameworks");
+    let frameworks_dir = frameworks_dir(context);
 
-    io::create_dir(&frameworks_dir)?;
+    io::create_dir(context, &frameworks_dir)?;
     setup_sdl2(context, &frameworks_dir)?;
     setup_sdl2_image(context, &frameworks_dir)?;
 
     Ok(frameworks_dir)
 }
 
+#[allow(clippy::ptr_arg)]
 fn setup_sdl2(context: &Context, frameworks_dir: &PathBuf) -> FailableUnit {
     let output_dir = frameworks_dir.join(SDL2_FRAMEWORK_NAME);
     if output_dir.exists() {
         return Ok(());
     }
 
-    remote_zips::fetch(SDL2_URL, SDL2_DIR, &context.working_dir)?;
+    remote_zips::fetch(context, &context.config.macos.sdl2_url, SDL2_DIR, &context.working_dir)?;
 
     let xcode_project_dir = context.working_dir.join(SDL2_DIR).join("Xcode").join("SDL");
     logs::out(log_tag!(), "Compiling Xcode framework for SDL2, this may take a while ...");
 
-    scripts::run(&Script::new(
+    scripts::run(context, &Script::new(
 		r#"xcodebuild archive -scheme Framework -destination "platform=macOS" -archivePath ./SDL2.xcarchive SKIP_INSTALL=NO BUILD_LIBRARY_FOR_DISTRIBUTION=YES"#
 	).working_dir(&xcode_project_dir))?;
 
     io::copy(
+        context,
         &xcode_project_dir.join("SDL2.xcarchive").join("Products").join("Library").join("Frameworks").join(SDL2_FRAMEWORK_NAME),
         &output_dir,
     )
@@ -51,27 +55,28 @@ fn setup_sdl2_image(context: &Context, frameworks_dir: &PathBuf) -> FailableUnit
         return Ok(());
     }
 
-    remote_zips::fetch(SDL2_IMAGE_URL, SDL2_IMAGE_DIR, &context.working_dir)?;
+    remote_zips::fetch(context, &context.config.macos.sdl2_image_url, SDL2_IMAGE_DIR, &context.working_dir)?;
 
     let custom_framework_dir = context.working_dir.join(SDL2_IMAGE_CUSTOM_FRAMEWORK_DIR);
 
-    io::delete(&custom_framework_dir)?;
-    io::create_dir(&custom_framework_dir)?;
-    io::create_symlink(&context.working_dir.join(SDL2_IMAGE_DIR), &PathBuf::from("Source"), &custom_framework_dir)?;
-    io::create_symlink(frameworks_dir, &PathBuf::from("Frameworks"), &custom_framework_dir)?;
-    io::write_string(SDL2_IMAGE_CUSTOM_FRAMEWORK_PROJECT_DEFINITION, &custom_framework_dir.join("project.yml"))?;
+    io::delete(context, &custom_framework_dir)?;
+    io::create_dir(context, &custom_framework_dir)?;
+    io::create_symlink(context, &context.working_dir.join(SDL2_IMAGE_DIR), &PathBuf::from("Source"), &custom_framework_dir)?;
+    io::create_symlink(context, frameworks_dir, &PathBuf::from("Frameworks"), &custom_framework_dir)?;
+    io::write_string(context, SDL2_IMAGE_CUSTOM_FRAMEWORK_PROJECT_DEFINITION, &custom_framework_dir.join("project.yml"))?;
 
-    scripts::run(&Script::new("xcodegen generate").working_dir(&custom_framework_dir))?;
+    scripts::run(context, &Script::new("xcodegen generate").working_dir(&custom_framework_dir))?;
 
     logs::out(log_tag!(), "Compiling custom Xcode framework for SDL2_image, this may take a while ...");
 
-    scripts::run(&Script::new(
+    scripts::run(context, &Script::new(
 		r#"xcodebuild archive -scheme SDL2_image -destination "platform=macOS" -archivePath ./SDL2_image.xcarchive SKIP_INSTALL=NO BUILD_LIBRARY_FOR_DISTRIBUTION=YES"#
 	).working_dir(&custom_framework_dir))?;
 
     logs::out(log_tag!(), "Copying SDL2_image.framework into Frameworks directory ...");
 
     io::copy(
+        context,
         &custom_framework_dir
             .join("SDL2_image.xcarchive")
             .join("Products")
@@ -2,30 +2,112 @@ pub mod core;
 pub mod log_tag;
 
 mod android;
+mod android_ndk;
+mod doctor;
 mod emscripten;
 mod ios;
+mod linux;
+mod linux_packaging;
 mod macos_console;
 mod macos_desktop;
 mod macos_sdl;
+mod targets;
 mod windows;
 
-use crate::core::{context::Context, failable_unit::FailableUnit, logs, target::Target, variant::Variant};
-use clap::{App, AppSettings, Arg};
-use std::path::PathBuf;
+use crate::core::{
+    build_plan::{self, RecordingExecutor},
+    build_report, build_target,
+    context::{Context, Options},
+    executor::{Executor, SystemExecutor},
+    failable::Failable,
+    failable_unit::FailableUnit,
+    io, logs, outputs,
+    target::Target,
+    variant::Variant,
+    versions,
+};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::{collections::BTreeMap, path::PathBuf, rc::Rc};
 
-#[cfg(target_os = "windows")]
-fn get_supported_platform_ids() -> Vec<&'static str> {
-    vec![Target::Android.id(), Target::Emscripten.id(), Target::Windows.id()]
+fn target_arg() -> Arg<'static, 'static> {
+    Arg::with_name("target")
+        .long("target")
+        .takes_value(true)
+        .required(true)
+        .possible_values(&targets::supported_ids())
+        .case_insensitive(true)
+        .help("Target:")
 }
 
-#[cfg(target_os = "macos")]
-fn get_supported_platform_ids() -> Vec<&'static str> {
+fn build_args() -> Vec<Arg<'static, 'static>> {
     vec![
-        Target::Android.id(),
-        Target::Emscripten.id(),
-        Target::Ios.id(),
-        Target::MacOSConsole.id(),
-        Target::MacOSDesktop.id(),
+        target_arg(),
+        Arg::with_name("variant")
+            .long("variant")
+            .takes_value(true)
+            .possible_values(&[Variant::Debug.id(), Variant::Release.id()])
+            .case_insensitive(true)
+            .default_value(Variant::Debug.id())
+            .help("Variant:"),
+        Arg::with_name("profile")
+            .long("profile")
+            .takes_value(true)
+            .conflicts_with("variant")
+            .help("Cargo profile to build with instead of a variant, for example 'release-lto' from crust-main/Cargo.toml."),
+        Arg::with_name("package")
+            .long("package")
+            .takes_value(true)
+            .help("Crate to build instead of the one in crust-build.toml, either its directory or its package name."),
+        Arg::with_name("bin").long("bin").takes_value(true).help("Binary of the crate to build, if it has more than one."),
+        Arg::with_name("features")
+            .long("features")
+            .takes_value(true)
+            .use_delimiter(true)
+            .help("Comma separated Cargo features to enable, passed on to every Rust compile."),
+        Arg::with_name("no-default-features")
+            .long("no-default-features")
+            .help("Don't enable the default Cargo features of the main crate."),
+        Arg::with_name("offline")
+            .long("offline")
+            .help("Never use the network, third party archives must already be in the download cache."),
+        Arg::with_name("update-lock")
+            .long("update-lock")
+            .conflicts_with("offline")
+            .help("Download third party archives without a pinned checksum and pin them in crust-build/downloads.lock."),
+        Arg::with_name("force")
+            .long("force")
+            .help("Run every build step even if its inputs haven't changed since the last build."),
+        Arg::with_name("abi")
+            .long("abi")
+            .takes_value(true)
+            .use_delimiter(true)
+            .possible_values(&["arm64-v8a", "armeabi-v7a", "x86", "x86_64"])
+            .help("Android only: comma separated ABIs to build instead of those in crust-build.toml."),
+        Arg::with_name("assemble")
+            .long("assemble")
+            .help("Android only: run Gradle afterwards to produce a debug APK or release AAB in the output directory."),
+        Arg::with_name("dry-run")
+            .long("dry-run")
+            .help("Print the scripts and file operations the command would perform without running them."),
+        Arg::with_name("plan-file")
+            .long("plan-file")
+            .takes_value(true)
+            .requires("dry-run")
+            .help("Write the dry run build plan as JSON to this file instead of printing it."),
+    ]
+}
+
+fn run_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("port").long("port").takes_value(true).help("Emscripten only: port for the built in web server."),
+        Arg::with_name("bind")
+            .long("bind")
+            .takes_value(true)
+            .help("Emscripten only: address for the built in web server to listen on, for example 0.0.0.0 on a remote machine."),
+        Arg::with_name("no-open").long("no-open").help("Emscripten only: don't open a web browser, useful on headless machines."),
+        Arg::with_name("watch")
+            .long("watch")
+            .help("Emscripten only: rebuild whenever the source code or assets change while the web server is running."),
     ]
 }
 
@@ -36,28 +118,25 @@ fn main() {
         .version("1.0.0")
         .author("Marcel Braghetto")
         .about("CLI for building 'CRUST' targets.")
-        .setting(AppSettings::ArgRequiredElseHelp)
-        .arg(
-            Arg::with_name("target")
-                .long("target")
-                .takes_value(true)
-                .required(true)
-                .possible_values(&get_supported_platform_ids())
-                .case_insensitive(true)
-                .help("Target:"),
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("build").about("Build a target.").args(&build_args()))
+        .subcommand(
+            SubCommand::with_name("clean").about("Delete the build and output directories of a target.").arg(target_arg()),
+        )
+        .subcommand(SubCommand::with_name("run").about("Build a target then launch it.").args(&build_args()).args(&run_args()))
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about("Check the toolchains and environment needed to build a target.")
+                .arg(target_arg()),
         )
-        .arg(
-            Arg::with_name("variant")
-                .long("variant")
-                .takes_value(true)
-                .possible_values(&[Variant::Debug.id(), Variant::Release.id()])
-                .case_insensitive(true)
-                .default_value(Variant::Debug.id())
-                .help("Variant:"),
+        .subcommand(
+            SubCommand::with_name("package")
+                .about("Build a target then create a distributable archive of its output.")
+                .args(&build_args()),
         )
         .get_matches();
 
-    std::process::exit(match build(&cli) {
+    std::process::exit(match run(&cli) {
         Ok(_) => 0,
         Err(err) => {
             logs::out(log_tag!(), &format!("Fatal error: {:?}", err));
@@ -66,7 +145,34 @@ fn main() {
     });
 }
 
-fn build(cli: &clap::ArgMatches) -> FailableUnit {
+fn run(cli: &ArgMatches) -> FailableUnit {
+    let (command, command_cli) = match cli.subcommand() {
+        (command, Some(command_cli)) => (command, command_cli),
+        _ => return Err("Missing command.".into()),
+    };
+
+    if !command_cli.is_present("dry-run") {
+        return execute(command, command_cli, Rc::new(SystemExecutor));
+    }
+
+    // A dry run still asks the host the same questions a build would, so the plan matches what this machine would do.
+    let recorder = Rc::new(RecordingExecutor::new(BTreeMap::new(), true));
+    execute(command, command_cli, recorder.clone())?;
+    let steps = recorder.steps();
+
+    match command_cli.value_of("plan-file") {
+        Some(plan_file) => {
+            logs::out(log_tag!(), &format!("Writing build plan to: {:?}", plan_file));
+            Ok(std::fs::write(plan_file, build_plan::to_json(&steps)?)?)
+        }
+        _ => {
+            build_plan::print(&steps);
+            Ok(())
+        }
+    }
+}
+
+fn create_context(cli: &ArgMatches, executor: Rc<dyn Executor>) -> Failable<Context> {
     let current_dir = match std::env::var("CARGO_MANIFEST_DIR") {
         Ok(manifest_path) => PathBuf::from(manifest_path),
         _ => {
@@ -75,15 +181,81 @@ fn build(cli: &clap::ArgMatches) -> FailableUnit {
     };
 
     let target = Target::resolve(cli.value_of("target").ok_or("Target arg not found.")?)?;
-    let variant = Variant::resolve(cli.value_of("variant").ok_or("Variant arg not found.")?)?;
-    let context = Context::new(current_dir.parent().ok_or("Missing parent dir")?.to_path_buf(), target, variant);
-
-    match target {
-        Target::Android => android::build(&context),
-        Target::Emscripten => emscripten::build(&context),
-        Target::Ios => ios::build(&context),
-        Target::MacOSConsole => macos_console::build(&context),
-        Target::MacOSDesktop => macos_desktop::build(&context),
-        Target::Windows => windows::build(&context),
+    let variant = Variant::resolve(cli.value_of("variant").unwrap_or_else(|| Variant::Debug.id()))?;
+
+    let options = Options {
+        profile: cli.value_of("profile"),
+        package: cli.value_of("package"),
+        binary: cli.value_of("bin"),
+        features: cli
+            .values_of("features")
+            .map(|features| features.map(|feature| feature.to_owned()).collect())
+            .unwrap_or_default(),
+        no_default_features: cli.is_present("no-default-features"),
+        offline: cli.is_present("offline"),
+        update_lock: cli.is_present("update-lock"),
+        force: cli.is_present("force"),
+        assemble: cli.is_present("assemble"),
+        android_abis: cli.values_of("abi").map(|abis| abis.map(|abi| abi.to_owned()).collect()),
+        emscripten_port: match cli.value_of("port") {
+            Some(port) => Some(port.parse().map_err(|_| format!("Invalid port: {}", port))?),
+            None => None,
+        },
+        emscripten_bind_address: cli.value_of("bind"),
+        emscripten_no_open: cli.is_present("no-open"),
+        emscripten_watch: cli.is_present("watch"),
+        executor,
+        ..Options::new(target, variant)
+    };
+
+    Context::new(current_dir.parent().ok_or("Missing parent dir")?.to_path_buf(), options)
+}
+
+fn execute(command: &str, cli: &ArgMatches, executor: Rc<dyn Executor>) -> FailableUnit {
+    let context = create_context(cli, executor)?;
+
+    match command {
+        "build" => build(&context),
+        "clean" => clean(&context),
+        "run" => {
+            build(&context)?;
+            launch(&context)
+        }
+        "doctor" => doctor::check(&context),
+        "package" => {
+            build(&context)?;
+            package(&context)
+        }
+        _ => Err(format!("Unknown command: {}", command).into()),
+    }
+}
+
+fn build(context: &Context) -> FailableUnit {
+    context.require_binary()?;
+    let version = versions::stamp(context)?;
+
+    // The report is written even when the build fails, so the failing step and script can be found in it.
+    build_report::start(context, &version);
+    let result = build_target::build(targets::resolve(context.target)?.as_ref(), context);
+
+    // A report that can't be written is worth knowing about, but never at the cost of the build's own error.
+    if let Err(error) = build_report::finish(context, &result) {
+        logs::out(log_tag!(), &format!("Unable to write the build report: {:?}", error));
     }
+
+    result
+}
+
+fn clean(context: &Context) -> FailableUnit {
+    logs::out(log_tag!(), &format!("Cleaning target: {}", context.target.id()));
+    io::delete(context, &context.working_dir)?;
+    io::delete(context, &outputs::outputs_root_dir(context))
+}
+
+fn package(context: &Context) -> FailableUnit {
+    build_target::package(targets::resolve(context.target)?.as_ref(), context)
+}
+
+fn launch(context: &Context) -> FailableUnit {
+    targets::resolve(context.target)?.run(context)
 }
@@ -0,0 +1,39 @@
+use crate::{
+    android::AndroidTarget,
+    core::{build_target::BuildTarget, failable::Failable, target::Target},
+    emscripten::EmscriptenTarget,
+    ios::IosTarget,
+    linux::LinuxTarget,
+    macos_console::MacOSConsoleTarget,
+    macos_desktop::MacOSDesktopTarget,
+    windows::WindowsTarget,
+};
+
+// Adding a platform means implementing 'BuildTarget' for it and listing it here, main.rs never needs to know about it.
+// Each build gets a fresh instance since targets keep what their dependencies phase resolved for the phases after it.
+const BUILD_TARGETS: &[fn() -> Box<dyn BuildTarget>] = &[
+    || Box::new(AndroidTarget::default()),
+    || Box::new(EmscriptenTarget),
+    || Box::new(IosTarget),
+    || Box::new(LinuxTarget::default()),
+    || Box::new(MacOSConsoleTarget),
+    || Box::new(MacOSDesktopTarget),
+    || Box::new(WindowsTarget),
+];
+
+pub fn supported_ids() -> Vec<&'static str> {
+    BUILD_TARGETS
+        .iter()
+        .map(|create| create())
+        .filter(|build_target| build_target.is_supported_on_host())
+        .map(|build_target| build_target.target().id())
+        .collect()
+}
+
+pub fn resolve(target: Target) -> Failable<Box<dyn BuildTarget>> {
+    BUILD_TARGETS
+        .iter()
+        .map(|create| create())
+        .find(|build_target| build_target.target() == target)
+        .ok_or_else(|| format!("No build target registered for '{}'", target.id()).into())
+}
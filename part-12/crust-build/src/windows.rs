@@ -1,84 +1,161 @@
 use crate::{
     core::{
-        context::Context, failable::Failable, failable_unit::FailableUnit, io, logs, outputs, remote_zips, script::Script,
-        scripts, variant::Variant,
+        assets, build_target::BuildTarget, context::Context, failable::Failable, failable_unit::FailableUnit,
+        fingerprint::Fingerprint, logs, outputs, remote_zips, script::Script, scripts, target::Target,
     },
     log_tag,
 };
 use std::path::PathBuf;
 
-const SDL2_URL: &str = "https://www.libsdl.org/release/SDL2-devel-2.0.14-VC.zip";
 const SDL2_DIR: &str = "sdl2";
 
-const SDL2_IMAGE_URL: &str = "https://www.libsdl.org/projects/SDL_image/release/SDL2_image-devel-2.0.5-VC.zip";
 const SDL2_IMAGE_DIR: &str = "sdl2-image";
 
-pub fn build(context: &Context) -> FailableUnit {
-    context.print_summary();
-    let sdl2_libs_dir = setup_sdl2(context)?;
-    let sdl2_image_libs_dir = setup_sdl2_image(context)?;
-    compile(context, &sdl2_libs_dir, &sdl2_image_libs_dir)?;
-    create_output(context, &sdl2_libs_dir, &sdl2_image_libs_dir)
+const SDL2_MINGW_DIR: &str = "sdl2-mingw";
+
+const SDL2_IMAGE_MINGW_DIR: &str = "sdl2-image-mingw";
+
+// The Rust target we cross compile to from non Windows hosts, and the directory holding the matching libraries in the MinGW SDL archives.
+pub const GNU_RUST_TRIPLE: &str = "x86_64-pc-windows-gnu";
+const MINGW_TRIPLE: &str = "x86_64-w64-mingw32";
+
+pub struct WindowsTarget;
+
+impl BuildTarget for WindowsTarget {
+    fn target(&self) -> Target {
+        Target::Windows
+    }
+
+    fn is_supported_on_host(&self) -> bool {
+        cfg!(target_os = "windows") || cfg!(target_os = "linux")
+    }
+
+    fn prepare_dependencies(&self, context: &Context) -> FailableUnit {
+        if !uses_gnu_toolchain() {
+            remote_zips::fetch(context, &context.config.windows.sdl2_url, SDL2_DIR, &context.working_dir)?;
+            return remote_zips::fetch(context, &context.config.windows.sdl2_image_url, SDL2_IMAGE_DIR, &context.working_dir);
+        }
+
+        logs::out(log_tag!(), &format!("Cross compiling for Windows via '{}' ...", GNU_RUST_TRIPLE));
+        scripts::run(context, &Script::new(&format!("rustup target add {}", GNU_RUST_TRIPLE)).retries(2))?;
+        remote_zips::fetch(context, &context.config.windows.mingw_sdl2_url, SDL2_MINGW_DIR, &context.working_dir)?;
+        remote_zips::fetch(context, &context.config.windows.mingw_sdl2_image_url, SDL2_IMAGE_MINGW_DIR, &context.working_dir)
+    }
+
+    fn compile(&self, context: &Context) -> FailableUnit {
+        compile(context, &sdl2_libs_dir(context), &sdl2_image_libs_dir(context))
+    }
+
+    fn output_files(&self, context: &Context) -> Failable<Vec<PathBuf>> {
+        let mut output_files = vec![binary_path(context)];
+        output_files.extend(dlls(context));
+        Ok(output_files)
+    }
+
+    fn output_fingerprint(&self, context: &Context, fingerprint: Fingerprint) -> Failable<Fingerprint> {
+        fingerprint.file(&assets::pack_path(context))
+    }
+
+    fn complete_output(&self, context: &Context) -> FailableUnit {
+        outputs::link_or_collect(context, &assets::pack_path(context), assets::PACK_FILE_NAME)
+    }
+
+    fn run(&self, context: &Context) -> FailableUnit {
+        let binary_file_name = format!("{}.exe", &context.binary_name);
+
+        if cfg!(target_os = "windows") {
+            return outputs::launch(context, &binary_file_name);
+        }
+
+        // A cross compiled build can at least be smoke tested on the build machine itself.
+        let binary_path = outputs::output_dir(context).join(&binary_file_name);
+        logs::out(log_tag!(), &format!("Launching under Wine: {:?}", &binary_path));
+        scripts::run(context, &Script::new(&format!("wine {:?}", &binary_path)))
+    }
 }
 
-fn setup_sdl2(context: &Context) -> Failable<PathBuf> {
-    remote_zips::fetch(SDL2_URL, SDL2_DIR, &context.working_dir)?;
-    Ok(context.working_dir.join(SDL2_DIR).join("lib").join("x64"))
+pub fn uses_gnu_toolchain() -> bool {
+    // Windows hosts build with MSVC against the Visual C++ SDL archives, any other host cross compiles with MinGW.
+    !cfg!(target_os = "windows")
 }
 
-fn setup_sdl2_image(context: &Context) -> Failable<PathBuf> {
-    remote_zips::fetch(SDL2_IMAGE_URL, SDL2_IMAGE_DIR, &context.working_dir)?;
-    Ok(context.working_dir.join(SDL2_IMAGE_DIR).join("lib").join("x64"))
+fn sdl2_libs_dir(context: &Context) -> PathBuf {
+    if uses_gnu_toolchain() {
+        context.working_dir.join(SDL2_MINGW_DIR).join(MINGW_TRIPLE).join("lib")
+    } else {
+        context.working_dir.join(SDL2_DIR).join("lib").join("x64")
+    }
 }
 
-fn compile(context: &Context, sdl2_libs_dir: &PathBuf, sdl2_image_libs_dir: &PathBuf) -> FailableUnit {
-    logs::out(log_tag!(), "Compiling application ...");
+fn sdl2_image_libs_dir(context: &Context) -> PathBuf {
+    if uses_gnu_toolchain() {
+        context.working_dir.join(SDL2_IMAGE_MINGW_DIR).join(MINGW_TRIPLE).join("lib")
+    } else {
+        context.working_dir.join(SDL2_IMAGE_DIR).join("lib").join("x64")
+    }
+}
 
-    // When we compile our Rust code we will add extra linker search paths using the `-L` flag, so our build
-    // can locate the appropriate SDL `.lib` files to link against. Note that the `.lib` file doesn't contain
-    // the implementation - that is what the `.dll` files do and we'll collect them later in the build process.
-    // If you add more external libraries you need to add a search path to the location of their .lib files too.
-    scripts::run(&Script::new(&format!(
-        r#"cargo rustc {} --manifest-path {:?} --bin crust --target-dir {:?} -- -L {:?} -L {:?}"#,
-        context.variant.rust_compiler_flag(),
-        context.source_dir.join("Cargo.toml"),
-        context.rust_build_dir,
-        sdl2_libs_dir,
-        sdl2_image_libs_dir,
-    )))?;
+fn binary_path(context: &Context) -> PathBuf {
+    let binary_file_name = format!("{}.exe", &context.binary_name);
 
-    logs::out(log_tag!(), "Compile completed successfully!");
+    // Cargo only nests the output under the Rust triple when a '--target' was given, which is the case for cross compiling.
+    if uses_gnu_toolchain() {
+        context.rust_build_dir.join(GNU_RUST_TRIPLE).join(context.profile.id()).join(binary_file_name)
+    } else {
+        context.rust_build_dir.join(context.profile.id()).join(binary_file_name)
+    }
+}
 
-    Ok(())
+fn dlls(context: &Context) -> Vec<PathBuf> {
+    // The MSVC archives keep their DLLs next to the import libraries, the MinGW archives put them in a sibling 'bin' directory.
+    let (sdl2_dlls_dir, sdl2_image_dlls_dir) = if uses_gnu_toolchain() {
+        (
+            context.working_dir.join(SDL2_MINGW_DIR).join(MINGW_TRIPLE).join("bin"),
+            context.working_dir.join(SDL2_IMAGE_MINGW_DIR).join(MINGW_TRIPLE).join("bin"),
+        )
+    } else {
+        (sdl2_libs_dir(context), sdl2_image_libs_dir(context))
+    };
+
+    vec![
+        sdl2_dlls_dir.join("SDL2.dll"),
+        sdl2_image_dlls_dir.join("SDL2_image.dll"),
+        sdl2_image_dlls_dir.join("libpng16-16.dll"),
+        sdl2_image_dlls_dir.join("zlib1.dll"),
+    ]
 }
 
-fn create_output(context: &Context, sdl2_libs_dir: &PathBuf, sdl2_image_libs_dir: &PathBuf) -> FailableUnit {
-    logs::out(log_tag!(), "Creating product ...");
+fn compile(context: &Context, sdl2_libs_dir: &PathBuf, sdl2_image_libs_dir: &PathBuf) -> FailableUnit {
+    logs::out(log_tag!(), "Compiling application ...");
 
-    outputs::clean(context)?;
+    // Rust already defaults to the 'x86_64-w64-mingw32-gcc' linker for this target, so naming the target is all cross compiling needs.
+    let target_arg = if uses_gnu_toolchain() {
+        format!("--target {} ", GNU_RUST_TRIPLE)
+    } else {
+        String::new()
+    };
 
-    outputs::collect(
+    // When we compile our Rust code we will add extra linker search paths using the `-L` flag, so our build
+    // can locate the appropriate SDL `.lib` files to link against. Note that the `.lib` file doesn't contain
+    // the implementation - that is what the `.dll` files do and we'll collect them later in the build process.
+    // If you add more external libraries you need to add a search path to the location of their .lib files too.
+    // The MinGW archives provide '.dll.a' import libraries instead, which serve exactly the same purpose.
+    scripts::run(
         context,
-        vec![
-            context.rust_build_dir.join(context.variant.id()).join("crust.exe"),
-            sdl2_libs_dir.join("SDL2.dll"),
-            sdl2_image_libs_dir.join("SDL2_image.dll"),
-            sdl2_image_libs_dir.join("libpng16-16.dll"),
-            sdl2_image_libs_dir.join("zlib1.dll"),
-        ],
+        &Script::new(&format!(
+            r#"cargo rustc {} --manifest-path {:?} {}--bin {} --target-dir {:?} -- -L {:?} -L {:?}"#,
+            context.cargo_build_flags(),
+            context.source_dir.join("Cargo.toml"),
+            target_arg,
+            &context.binary_name,
+            context.rust_build_dir,
+            sdl2_libs_dir,
+            sdl2_image_libs_dir,
+        ))
+        .log_file(&scripts::log_file(context, "compile")),
     )?;
 
-    match context.variant {
-        Variant::Debug => {
-            logs::out(log_tag!(), "Debug build - symlinking assets ...");
-            io::create_symlink(&context.assets_dir, &outputs::output_dir(context).join("assets"), &outputs::output_dir(context))?;
-        }
-
-        Variant::Release => {
-            logs::out(log_tag!(), "Release build - collecting assets ...");
-            outputs::collect(context, vec![context.assets_dir.clone()])?;
-        }
-    }
+    logs::out(log_tag!(), "Compile completed successfully!");
 
     Ok(())
 }
@@ -1,22 +1,25 @@
-pub struct TextureData<'a> {
+// How the pixels are laid out in memory, which decides the format and type they are uploaded to the GPU with.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PixelLayout {
+    Rgba8,
+    Rgb565,
+    Rgba4444,
+}
+
+pub struct TextureData {
     pub width: u32,
     pub height: u32,
-    surface: sdl2::surface::Surface<'a>,
+    pub layout: PixelLayout,
+    pub pixels: Vec<u8>,
 }
 
-impl<'a> TextureData<'a> {
-    pub fn new(surface: sdl2::surface::Surface) -> TextureData {
-        let width = surface.width();
-        let height = surface.height();
-
+impl TextureData {
+    pub fn new(width: u32, height: u32, layout: PixelLayout, pixels: Vec<u8>) -> TextureData {
         TextureData {
-            surface: surface,
             width: width,
             height: height,
+            layout: layout,
+            pixels: pixels,
         }
     }
-
-    pub fn surface(&mut self) -> &sdl2::surface::Surface {
-        &self.surface
-    }
 }
@@ -1,22 +1,37 @@
 use crate::{
-    components::{mesh_data::MeshData, texture_data::TextureData, vertex::Vertex},
-    core::failable::Failable,
-};
-use sdl2::{pixels::PixelFormatEnum, rwops::RWops, surface::Surface};
-use std::{
-    collections::HashMap,
-    io::{BufReader, Read},
-    path::Path,
-    vec::Vec,
+    components::{
+        mesh_data::MeshData,
+        texture_data::{PixelLayout, TextureData},
+        vertex::Vertex,
+    },
+    core::{failable::Failable, vfs},
 };
+use sdl2::{image::ImageRWops, pixels::PixelFormatEnum, rwops::RWops, surface::Surface};
+use std::{collections::HashMap, io::BufReader, path::Path, vec::Vec};
 
+// Every path is resolved through the mounted asset packs and directories rather than the working directory.
 pub fn load_text_file(path: &str) -> Failable<String> {
-    let mut stream = RWops::from_file(Path::new(path), "r")?;
-    let mut content = String::new();
+    Ok(String::from_utf8(vfs::read(path)?)?)
+}
+
+// Builds made by crust-build ship a processed 'name.mesh' in place of 'name.obj' (and 'name.texture' for 'name.png'), the
+// original file is only loaded where the assets are bundled as they are, for example by the Xcode projects.
+fn processed_path(path: &str, extension: &str) -> String {
+    Path::new(path).with_extension(extension).to_string_lossy().to_string()
+}
 
-    stream.read_to_string(&mut content)?;
+pub fn load_mesh(path: &str) -> Failable<MeshData> {
+    match vfs::read_if_exists(&processed_path(path, "mesh"))? {
+        Some(data) => read_mesh(&data).map_err(|error| format!("Invalid mesh for {}: {}", path, error).into()),
+        None => load_obj_file(path),
+    }
+}
 
-    Ok(content)
+pub fn load_texture(path: &str) -> Failable<TextureData> {
+    match vfs::read_if_exists(&processed_path(path, "texture"))? {
+        Some(data) => read_texture(&data).map_err(|error| format!("Invalid texture for {}: {}", path, error).into()),
+        None => load_png(path),
+    }
 }
 
 pub fn load_obj_file(path: &str) -> Failable<MeshData> {
@@ -53,6 +68,111 @@ pub fn load_obj_file(path: &str) -> Failable<MeshData> {
 }
 
 pub fn load_png(path: &str) -> Failable<TextureData> {
-    let surface: Surface = sdl2::image::LoadSurface::from_file(Path::new(path))?;
-    Ok(TextureData::new(surface.convert_format(PixelFormatEnum::RGBA32)?))
+    let content = vfs::read(path)?;
+    let surface: Surface = RWops::from_bytes(&content)?.load_png()?;
+    let surface = surface.convert_format(PixelFormatEnum::RGBA32)?;
+    let (width, height, pitch) = (surface.width() as usize, surface.height() as usize, surface.pitch() as usize);
+    let data = surface.without_lock().ok_or("Texture surface needs locking")?;
+    let mut pixels = Vec::with_capacity(width * height * 4);
+
+    // Rows can be padded in the surface, the texture data is always tightly packed.
+    for row in 0..height {
+        pixels.extend_from_slice(&data[row * pitch..row * pitch + width * 4]);
+    }
+
+    Ok(TextureData::new(width as u32, height as u32, PixelLayout::Rgba8, pixels))
+}
+
+// The formats below are written by crust-build ('crust-build/src/core/meshes.rs' and 'textures.rs'), all numbers are little endian.
+const HEADER_SIZE: usize = 20;
+const VERTEX_SIZE: usize = 20;
+
+fn read_u32(data: &[u8], offset: usize) -> Failable<u32> {
+    let bytes = data.get(offset..offset + 4).ok_or("Unexpected end of data")?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_header(data: &[u8], magic: &[u8]) -> Failable<()> {
+    if data.get(0..4) != Some(magic) || read_u32(data, 4)? != 1 {
+        return Err("Unknown format or version".into());
+    }
+
+    Ok(())
+}
+
+fn read_mesh(data: &[u8]) -> Failable<MeshData> {
+    read_header(data, b"CMSH")?;
+
+    let vertex_count = read_u32(data, 8)? as usize;
+    let index_count = read_u32(data, 12)? as usize;
+    let index_size = read_u32(data, 16)? as usize;
+
+    if index_size != 2 && index_size != 4 {
+        return Err(format!("Unsupported index size {}", index_size).into());
+    }
+
+    // The counts come from the file itself, so a damaged one must not get as far as allocating or reading anything for them.
+    let indices_offset = checked_size(&[vertex_count, VERTEX_SIZE], HEADER_SIZE).ok_or("Mesh is too large")?;
+    let mesh_size = checked_size(&[index_count, index_size], indices_offset).ok_or("Mesh is too large")?;
+
+    if data.len() < mesh_size {
+        return Err("Unexpected end of data".into());
+    }
+
+    let mut vertices: Vec<Vertex> = Vec::with_capacity(vertex_count);
+    let mut indices: Vec<u32> = Vec::with_capacity(index_count);
+
+    for vertex in 0..vertex_count {
+        let mut values = [0.; 5];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = f32::from_bits(read_u32(data, HEADER_SIZE + vertex * VERTEX_SIZE + i * 4)?);
+        }
+
+        vertices.push(Vertex {
+            position: glm::vec3(values[0], values[1], values[2]),
+            texture_coord: glm::vec2(values[3], values[4]),
+        });
+    }
+
+    for index in 0..index_count {
+        let offset = indices_offset + index * index_size;
+
+        indices.push(match index_size {
+            2 => {
+                let bytes = data.get(offset..offset + 2).ok_or("Unexpected end of data")?;
+                u16::from_le_bytes([bytes[0], bytes[1]]) as u32
+            }
+            _ => read_u32(data, offset)?,
+        });
+    }
+
+    Ok(MeshData {
+        vertices: vertices,
+        indices: indices,
+    })
+}
+
+fn read_texture(data: &[u8]) -> Failable<TextureData> {
+    read_header(data, b"CTEX")?;
+
+    let (layout, bytes_per_pixel) = match read_u32(data, 8)? {
+        0 => (PixelLayout::Rgba8, 4),
+        1 => (PixelLayout::Rgb565, 2),
+        2 => (PixelLayout::Rgba4444, 2),
+        format => return Err(format!("Unsupported pixel format {}", format).into()),
+    };
+
+    let width = read_u32(data, 12)?;
+    let height = read_u32(data, 16)?;
+    let pixels_end =
+        checked_size(&[width as usize, height as usize, bytes_per_pixel], HEADER_SIZE).ok_or("Texture is too large")?;
+    let pixels = data.get(HEADER_SIZE..pixels_end).ok_or("Unexpected end of data")?.to_vec();
+
+    Ok(TextureData::new(width, height, layout, pixels))
+}
+
+// The offset plus the product of the factors, or 'None' if that overflows, which is easy on 32 bit targets such as
+// Android's armeabi-v7a.
+fn checked_size(factors: &[usize], offset: usize) -> Option<usize> {
+    factors.iter().try_fold(1usize, |size, factor| size.checked_mul(*factor))?.checked_add(offset)
 }
@@ -43,8 +43,10 @@ impl<T: Engine> MainLoop<T> {
 
     pub fn run(&mut self) -> Failable<bool> {
         for event in self.event_pump.poll_iter() {
+            // SDL follows every user resize with a size change, which is also the only event a browser canvas resize produces,
+            // so listening for size changes alone handles each resize exactly once.
             if let Event::Window {
-                win_event: WindowEvent::Resized { .. },
+                win_event: WindowEvent::SizeChanged { .. },
                 ..
             } = event
             {
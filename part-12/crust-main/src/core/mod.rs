@@ -8,6 +8,8 @@ pub mod logs;
 pub mod main_loop;
 pub mod renderer;
 pub mod scene;
+pub mod version;
+pub mod vfs;
 pub mod window;
 
 #[cfg(not(target_os = "emscripten"))]
@@ -0,0 +1,19 @@
+// crust-build passes these in when it compiles us, a plain 'cargo build' falls back to the Cargo.toml version.
+pub const VERSION: &str = match option_env!("CRUST_VERSION") {
+    Some(version) => version,
+    None => env!("CARGO_PKG_VERSION"),
+};
+
+pub const BUILD_NUMBER: &str = match option_env!("CRUST_BUILD_NUMBER") {
+    Some(build_number) => build_number,
+    None => "0",
+};
+
+pub const GIT_DESCRIBE: &str = match option_env!("CRUST_GIT_DESCRIBE") {
+    Some(git_describe) => git_describe,
+    None => "unknown",
+};
+
+pub fn describe() -> String {
+    format!("{} (build {}, {})", VERSION, BUILD_NUMBER, GIT_DESCRIBE)
+}
@@ -0,0 +1,150 @@
+use crate::{
+    core::{failable::Failable, failable_unit::FailableUnit, logs},
+    log_tag,
+};
+use sdl2::rwops::RWops;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+// The asset pack written by crust-build ('crust-build/src/core/asset_pack.rs'), found beside the application.
+const PACK_FILE_NAME: &str = "assets.pack";
+
+enum Mount {
+    Directory(PathBuf),
+    Pack {
+        path: PathBuf,
+        entries: HashMap<String, (u64, u64)>,
+    },
+}
+
+// Loaders can be called from anywhere, so the mounts are held statically the same way the Emscripten launcher holds its
+// state. Mounts are searched in the order they were added, so earlier mounts win when more than one has the same path.
+thread_local!(static MOUNTS: RefCell<Vec<Mount>> = RefCell::new(Vec::new()));
+
+// Loose files next to the application come first so they can override what is in the pack, the Xcode projects only
+// bundle loose files and have no pack at all.
+pub fn mount_defaults() {
+    let base_dir = base_dir();
+    mount_directory(&base_dir);
+
+    if let Err(error) = mount_pack(&base_dir.join(PACK_FILE_NAME)) {
+        logs::out(log_tag!(), &format!("Not mounting asset pack: {}", error));
+    }
+}
+
+// Desktop builds look beside the executable (or in the bundle's resources), so they work whatever directory they are started from.
+#[cfg(not(any(target_os = "android", target_os = "emscripten")))]
+fn base_dir() -> PathBuf {
+    match sdl2::filesystem::base_path() {
+        Ok(base_path) => PathBuf::from(base_path),
+        Err(error) => {
+            logs::out(log_tag!(), &format!("Unable to find the application directory, using the working directory: {}", error));
+            PathBuf::from(".")
+        }
+    }
+}
+
+// SDL reads relative paths from inside the APK on Android, through an asset manager which doesn't understand './', and the
+// Emscripten file system holds the preloaded files in the working directory. Both are given paths like 'assets.pack' as is.
+#[cfg(any(target_os = "android", target_os = "emscripten"))]
+fn base_dir() -> PathBuf {
+    PathBuf::new()
+}
+
+pub fn mount_directory(root: &Path) {
+    MOUNTS.with(|it| it.borrow_mut().push(Mount::Directory(root.to_path_buf())));
+}
+
+pub fn mount_pack(path: &Path) -> FailableUnit {
+    let mut stream = RWops::from_file(path, "r")?;
+    let entries = read_index(&mut stream).map_err(|error| format!("Invalid asset pack {:?}: {}", path, error))?;
+
+    logs::out(log_tag!(), &format!("Mounted asset pack {:?} with {} entries", path, entries.len()));
+
+    MOUNTS.with(|it| {
+        it.borrow_mut().push(Mount::Pack {
+            path: path.to_path_buf(),
+            entries: entries,
+        })
+    });
+
+    Ok(())
+}
+
+pub fn read(path: &str) -> Failable<Vec<u8>> {
+    read_if_exists(path)?.ok_or_else(|| format!("File not found in any mount: {}", path).into())
+}
+
+// Only a file missing from every mount is 'None', a file that is there but can't be read is still an error.
+pub fn read_if_exists(path: &str) -> Failable<Option<Vec<u8>>> {
+    MOUNTS.with(|it| {
+        for mount in it.borrow().iter() {
+            match mount {
+                Mount::Directory(root) => {
+                    // SDL reads from inside the APK on Android, so even loose files have to go through it.
+                    if let Ok(mut stream) = RWops::from_file(root.join(path), "r") {
+                        let mut content = Vec::new();
+                        stream.read_to_end(&mut content)?;
+                        return Ok(Some(content));
+                    }
+                }
+                Mount::Pack {
+                    path: pack_path,
+                    entries,
+                } => {
+                    if let Some((offset, size)) = entries.get(path) {
+                        let mut stream = RWops::from_file(pack_path, "r")?;
+                        let mut content = vec![0; *size as usize];
+                        stream.seek(SeekFrom::Start(*offset))?;
+                        stream.read_exact(&mut content)?;
+                        return Ok(Some(content));
+                    }
+                }
+            }
+        }
+
+        Ok(None)
+    })
+}
+
+// "CPAK", u32 version, u32 entry count, then per entry a u32 path length, the path, a u64 offset and a u64 size, all
+// numbers are little endian.
+fn read_index(stream: &mut RWops) -> Failable<HashMap<String, (u64, u64)>> {
+    let mut magic = [0; 4];
+    stream.read_exact(&mut magic)?;
+
+    if &magic != b"CPAK" || read_u32(stream)? != 1 {
+        return Err("Unknown format or version".into());
+    }
+
+    let count = read_u32(stream)?;
+    let mut entries = HashMap::with_capacity(count as usize);
+
+    for _ in 0..count {
+        let mut path = vec![0; read_u32(stream)? as usize];
+        stream.read_exact(&mut path)?;
+
+        let offset = read_u64(stream)?;
+        let size = read_u64(stream)?;
+
+        entries.insert(String::from_utf8(path)?, (offset, size));
+    }
+
+    Ok(entries)
+}
+
+fn read_u32(stream: &mut RWops) -> Failable<u32> {
+    let mut bytes = [0; 4];
+    stream.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(stream: &mut RWops) -> Failable<u64> {
+    let mut bytes = [0; 8];
+    stream.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
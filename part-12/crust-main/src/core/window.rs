@@ -19,24 +19,26 @@ pub fn get_size(video: &sdl2::VideoSubsystem) -> Failable<DisplaySize> {
 
 #[cfg(target_os = "emscripten")]
 pub fn get_size(_: &sdl2::VideoSubsystem) -> Failable<DisplaySize> {
-    use std::os::raw::{c_char, c_int};
+    use std::os::raw::{c_char, c_double, c_int};
 
     extern "C" {
-        fn emscripten_run_script_int(code: *const c_char) -> c_int;
+        // https://emscripten.org/docs/api_reference/html5.h.html#c.emscripten_get_element_css_size
+        fn emscripten_get_element_css_size(target: *const c_char, width: *mut c_double, height: *mut c_double) -> c_int;
     }
 
-    unsafe {
-        let width: i32 = emscripten_run_script_int(b"document.getElementById('canvas').width;\0" as *const _ as *const c_char)
-            .try_into()
-            .map_err(|_| "Failed to get HTML canvas width!")?;
+    let mut width: c_double = 0.;
+    let mut height: c_double = 0.;
 
-        let height: i32 = emscripten_run_script_int(b"document.getElementById('canvas').height;\0" as *const _ as *const c_char)
-            .try_into()
-            .map_err(|_| "Failed to get HTML canvas height!")?;
+    // We use the size the canvas is displayed at rather than its 'width' and 'height' attributes, so a page that sizes
+    // the canvas through CSS (for example to fill the browser window) gets a window that matches what is on screen.
+    let result = unsafe { emscripten_get_element_css_size(b"#canvas\0" as *const _ as *const c_char, &mut width, &mut height) };
 
-        Ok(DisplaySize {
-            width: width,
-            height: height,
-        })
+    if result != 0 {
+        return Err("Failed to get HTML canvas size!".into());
     }
+
+    Ok(DisplaySize {
+        width: width as i32,
+        height: height as i32,
+    })
 }
@@ -4,7 +4,7 @@ pub mod log_tag;
 pub mod opengl;
 pub mod scenes;
 
-use crate::core::{failable_unit::FailableUnit, logs};
+use crate::core::{failable_unit::FailableUnit, logs, version, vfs};
 
 #[cfg(any(target_os = "android", target_os = "ios"))]
 #[no_mangle]
@@ -14,6 +14,10 @@ pub extern "C" fn SDL_main(_argc: libc::c_int, _argv: *const *const libc::c_char
 }
 
 pub fn main() {
+    logs::out(log_tag!(), &format!("Version: {}", version::describe()));
+
+    vfs::mount_defaults();
+
     std::process::exit(match launch() {
         Ok(_) => 0,
         Err(err) => {
@@ -13,7 +13,7 @@ pub struct Mesh {
 
 impl Mesh {
     pub fn new(path: &str) -> Failable<Self> {
-        let mesh_data = io::load_obj_file(path)?;
+        let mesh_data = io::load_mesh(path)?;
 
         Ok(Mesh {
             id_vertices: create_vertex_buffer(&mesh_data),
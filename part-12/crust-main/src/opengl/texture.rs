@@ -1,5 +1,5 @@
 use crate::{
-    components::texture_data::TextureData,
+    components::texture_data::{PixelLayout, TextureData},
     core::{failable::Failable, io},
 };
 use gl::types::{GLint, GLuint, GLvoid};
@@ -10,10 +10,10 @@ pub struct Texture {
 
 impl Texture {
     pub fn new(path: &str) -> Failable<Self> {
-        let mut texture_data = io::load_png(path)?;
+        let texture_data = io::load_texture(path)?;
 
         Ok(Texture {
-            id: create_texture(&mut texture_data),
+            id: create_texture(&texture_data),
         })
     }
 
@@ -32,24 +32,33 @@ impl Drop for Texture {
     }
 }
 
-fn create_texture(data: &mut TextureData) -> GLuint {
+fn create_texture(data: &TextureData) -> GLuint {
     let mut id: GLuint = 0;
 
+    // The 16 bit layouts are uploaded as native u16 values, which matches the little endian files on every platform we target.
+    let (format, pixel_type) = match data.layout {
+        PixelLayout::Rgba8 => (gl::RGBA, gl::UNSIGNED_BYTE),
+        PixelLayout::Rgb565 => (gl::RGB, gl::UNSIGNED_SHORT_5_6_5),
+        PixelLayout::Rgba4444 => (gl::RGBA, gl::UNSIGNED_SHORT_4_4_4_4),
+    };
+
     unsafe {
         gl::GenTextures(1, &mut id);
         gl::BindTexture(gl::TEXTURE_2D, id);
+        // Rows are tightly packed, which for the 16 bit layouts isn't necessarily a multiple of the default 4 byte alignment.
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
         gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
         gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
         gl::TexImage2D(
             gl::TEXTURE_2D,
             0,
-            gl::RGBA as GLint,
+            format as GLint,
             data.width as GLint,
             data.height as GLint,
             0,
-            gl::RGBA,
-            gl::UNSIGNED_BYTE,
-            data.surface().without_lock().unwrap().as_ptr() as *const GLvoid,
+            format,
+            pixel_type,
+            data.pixels.as_ptr() as *const GLvoid,
         );
     }
 